@@ -0,0 +1,15 @@
+use std::fs;
+
+// Version de `aegis-lang` réellement résolue dans Cargo.lock, exposée au serveur sous
+// `AEGIS_COMPILER_VERSION` (la crate n'en publie pas).
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    let lock = fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let version = lock
+        .split("[[package]]")
+        .find(|package| package.lines().any(|line| line.trim() == r#"name = "aegis-lang""#))
+        .and_then(|package| package.lines().find_map(|line| line.trim().strip_prefix("version = ")))
+        .map(|version| version.trim_matches('"').to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=AEGIS_COMPILER_VERSION={}", version);
+}
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+use tower_lsp::lsp_types::CompletionItemKind;
+
+use crate::symbols::SymbolInfo;
+use crate::undefined;
+
+/// Appel dont le nombre d'arguments ne correspond pas à la déclaration de la fonction, ou
+/// instanciation `new` qui ne correspond pas au constructeur (`init`) de la classe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArityMismatch<'a> {
+    // Ligne (base 0) de l'instruction qui contient l'appel
+    pub line: u32,
+    pub name: &'a str,
+    // Rang de l'appel parmi ceux de `name` (ou les `new name`) dans la même instruction (0 pour le premier)
+    pub occurrence: usize,
+    pub expected: usize,
+    pub found: usize,
+    pub constructor: bool,
+}
+
+/// Nombre de paramètres du constructeur propre à une classe (None : pas de `init`) et nom
+/// de sa classe parente.
+type Class<'a> = (Option<usize>, Option<&'a str>);
+
+/// Appels `nom(...)` dont le nombre d'arguments diffère du nombre de paramètres de `nom`,
+/// déclarée dans le document ou dans un fichier importé (`imported`). Par prudence, un nom
+/// n'est vérifié que s'il désigne sans ambiguïté une seule fonction : déclaré avec des
+/// nombres de paramètres différents, dans un namespace, ou aussi utilisé comme variable
+/// ou paramètre quelque part dans le document, il est ignoré.
+///
+/// Une classe sans `init` hérite du constructeur de sa parente, et n'a aucun paramètre si
+/// elle n'en a pas. Une classe déclarée deux fois ou dans un namespace n'est pas vérifiée,
+/// pas plus qu'une classe importée sans `init` (sa parente n'est pas connue).
+pub fn arity_mismatches<'a>(ast: &'a Value, imported: &[SymbolInfo]) -> Vec<ArityMismatch<'a>> {
+    let mut functions: HashMap<&str, HashSet<usize>> = HashMap::new();
+    let mut shadowed = HashSet::new();
+    declarations(ast, false, &mut functions, &mut shadowed);
+    for symbol in imported.iter().filter(|s| s.kind == CompletionItemKind::FUNCTION && s.namespace.is_none()) {
+        functions.entry(symbol.name.as_str()).or_default().insert(symbol.params.len());
+    }
+
+    let mut classes: HashMap<&str, Option<Class>> = HashMap::new();
+    collect_classes(ast, false, &mut classes);
+    let mut constructors: HashMap<&str, usize> = classes
+        .keys()
+        .filter(|name| !shadowed.contains(*name) && !functions.contains_key(*name))
+        .filter_map(|&name| Some((name, constructor_arity(&classes, name, &mut HashSet::new())?)))
+        .collect();
+    for symbol in imported.iter().filter(|s| s.kind == CompletionItemKind::METHOD && s.name == "init") {
+        if let Some(class) = symbol.container.as_deref()
+            && !classes.contains_key(class)
+            && imported.iter().any(|s| s.kind == CompletionItemKind::CLASS && s.name == class && s.namespace.is_none()) {
+            constructors.insert(class, symbol.params.len());
+        }
+    }
+
+    let arities: HashMap<&str, usize> = functions
+        .into_iter()
+        .filter(|(name, counts)| counts.len() == 1 && !shadowed.contains(name))
+        .filter_map(|(name, counts)| Some((name, counts.into_iter().next()?)))
+        .collect();
+
+    let mut found = Vec::new();
+    calls(ast, 0, &arities, &constructors, &mut HashMap::new(), &mut found);
+    found
+}
+
+/// Classes du document ; None pour un nom déclaré deux fois ou dans un namespace.
+fn collect_classes<'a>(node: &'a Value, in_namespace: bool, classes: &mut HashMap<&'a str, Option<Class<'a>>>) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            // ["class", line, "nom", {méthode: [params, body, ...]}, parent, ...]
+            if cmd == Some("class")
+                && let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
+                let init = arr.get(3).and_then(|methods| methods.get("init")).and_then(|init| init.get(0)).and_then(|v| v.as_array());
+                let class = (!in_namespace && !classes.contains_key(name)).then(|| (init.map(Vec::len), arr.get(4).and_then(|v| v.as_str())));
+                classes.insert(name, class);
+            }
+
+            let in_namespace = in_namespace || cmd == Some("namespace");
+            for item in arr {
+                collect_classes(item, in_namespace, classes);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                collect_classes(item, in_namespace, classes);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Paramètres du constructeur de `class`, hérité au besoin ; None si une classe de la
+/// chaîne n'est pas connue.
+fn constructor_arity<'a>(classes: &HashMap<&'a str, Option<Class<'a>>>, class: &'a str, seen: &mut HashSet<&'a str>) -> Option<usize> {
+    if !seen.insert(class) {
+        return None;
+    }
+    match *classes.get(class)?.as_ref()? {
+        (Some(params), _) => Some(params),
+        (None, Some(parent)) => constructor_arity(classes, parent, seen),
+        (None, None) => Some(0),
+    }
+}
+
+/// Nombre de paramètres de chaque fonction déclarée, et noms déclarés autrement (variables,
+/// paramètres, itérateurs...) ou dans un namespace.
+fn declarations<'a>(
+    node: &'a Value,
+    in_namespace: bool,
+    functions: &mut HashMap<&'a str, HashSet<usize>>,
+    shadowed: &mut HashSet<&'a str>,
+) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            let name = arr.get(2).and_then(|v| v.as_str());
+            match cmd {
+                // ["function", line, "nom", params, ret, body]
+                Some("function") => {
+                    if let (Some(name), Some(list)) = (name, arr.get(3).and_then(|v| v.as_array())) {
+                        if in_namespace {
+                            shadowed.insert(name);
+                        } else {
+                            functions.entry(name).or_default().insert(list.len());
+                        }
+                    }
+                    params(arr.get(3), shadowed);
+                },
+                Some("set" | "const" | "foreach") => shadowed.extend(name),
+                // ["try", line, body, err_var, catch_body]
+                Some("try") => shadowed.extend(arr.get(3).and_then(|v| v.as_str())),
+                // ["lambda", params, body]
+                Some("lambda") => params(arr.get(1), shadowed),
+                // ["class", line, "nom", {méthode: [params, body, ...]}, ...]
+                Some("class") => {
+                    for method in arr.get(3).and_then(|v| v.as_object()).into_iter().flat_map(|m| m.values()) {
+                        params(method.get(0), shadowed);
+                    }
+                },
+                _ => {}
+            }
+
+            let in_namespace = in_namespace || cmd == Some("namespace");
+            for item in arr {
+                declarations(item, in_namespace, functions, shadowed);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                declarations(item, in_namespace, functions, shadowed);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Paramètres `[["a", null], ["b", "int"]]`.
+fn params<'a>(params: Option<&'a Value>, names: &mut HashSet<&'a str>) {
+    for param in params.and_then(|v| v.as_array()).into_iter().flatten() {
+        names.extend(param.get(0).and_then(|v| v.as_str()));
+    }
+}
+
+/// Appels `["call", line, ["get", nom], args]` (instruction) ou `["call", ["get", nom], args]`
+/// (expression, la ligne est alors celle de l'instruction englobante), et instanciations
+/// `["new", ["get", nom], arg1, arg2...]`.
+fn calls<'a>(
+    node: &'a Value,
+    line: u32,
+    arities: &HashMap<&str, usize>,
+    constructors: &HashMap<&str, usize>,
+    seen: &mut HashMap<(u32, &'a str, bool), usize>,
+    found: &mut Vec<ArityMismatch<'a>>,
+) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            let statement_line = arr.get(1).and_then(|v| v.as_u64()).filter(|_| cmd.is_some_and(|c| undefined::STATEMENTS.contains(&c)));
+            let line = statement_line.map_or(line, |l| l.saturating_sub(1) as u32);
+
+            if cmd == Some("call") {
+                let rest = if statement_line.is_some() { &arr[2..] } else { &arr[1..] };
+                if let [callee, args] = rest
+                    && callee.get(0).and_then(|v| v.as_str()) == Some("get")
+                    && let (Some(name), Some(args)) = (callee.get(1).and_then(|v| v.as_str()), args.as_array()) {
+                    let occurrence = seen.entry((line, name, false)).or_default();
+                    if let Some(&expected) = arities.get(name)
+                        && expected != args.len() {
+                        found.push(ArityMismatch { line, name, occurrence: *occurrence, expected, found: args.len(), constructor: false });
+                    }
+                    *occurrence += 1;
+                }
+            }
+            if cmd == Some("new")
+                && let Some(callee) = arr.get(1)
+                && callee.get(0).and_then(|v| v.as_str()) == Some("get")
+                && let Some(name) = callee.get(1).and_then(|v| v.as_str()) {
+                let occurrence = seen.entry((line, name, true)).or_default();
+                let args = arr.len() - 2;
+                if let Some(&expected) = constructors.get(name)
+                    && expected != args {
+                    found.push(ArityMismatch { line, name, occurrence: *occurrence, expected, found: args, constructor: true });
+                }
+                *occurrence += 1;
+            }
+
+            for item in arr {
+                calls(item, line, arities, constructors, seen, found);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                calls(item, line, arities, constructors, seen, found);
+            }
+        },
+        _ => {}
+    }
+}
@@ -0,0 +1,32 @@
+/// Fonctions natives enregistrées par la VM (aegis-lang 0.4), appelables sans déclaration ni import.
+pub const NATIVES: &[&str] = &[
+    // Noyau
+    "len", "typeof", "is_instance", "to_str", "to_int", "to_float", "chr", "ord", "fmt",
+    "stdout", "stderr",
+    // Fichiers & chemins
+    "io_read", "io_write", "io_append", "io_delete", "io_exists", "io_clear",
+    "path_exists", "path_ext", "path_join",
+    // Système & processus
+    "sys_env", "sys_exit", "sys_fail", "proc_exec",
+    // Temps & dates
+    "time_now", "time_sleep", "date_now", "date_format",
+    // Mathématiques & aléatoire
+    "math_abs", "math_acos", "math_asin", "math_atan", "math_ceil", "math_cos", "math_floor",
+    "math_pow", "math_round", "math_sin", "math_sqrt", "math_tan",
+    "rand_int", "rand_float",
+    // Formats & encodages
+    "json_parse", "json_stringify", "b64_encode", "b64_decode", "hash_sha256",
+    "re_new", "re_match", "re_replace",
+    // Réseau
+    "http_get", "http_post",
+    "sock_bind", "sock_accept", "sock_connect", "sock_read", "sock_write", "sock_close",
+];
+
+/// Noms fournis par le runtime sans être des natives : l'instance courante et les
+/// arguments de la ligne de commande.
+const GLOBALS: &[&str] = &["this", "__ARGS__"];
+
+/// Vrai si `name` est défini par le runtime dans tout programme.
+pub fn is_builtin(name: &str) -> bool {
+    NATIVES.contains(&name) || GLOBALS.contains(&name)
+}
@@ -0,0 +1,88 @@
+use tower_lsp::lsp_types::{CompletionItemKind, Position, Range};
+
+use crate::line_index::LineIndex;
+use crate::references;
+use crate::symbols::SymbolInfo;
+
+/// Appel d'une fonction ou d'une méthode du document depuis le corps d'une autre.
+#[derive(Debug, Clone)]
+pub struct Call<'a> {
+    pub caller: &'a SymbolInfo,
+    pub callee: &'a SymbolInfo,
+    // Plage du nom appelé
+    pub range: Range,
+}
+
+fn is_callable(symbol: &SymbolInfo) -> bool {
+    matches!(symbol.kind, CompletionItemKind::FUNCTION | CompletionItemKind::METHOD)
+}
+
+/// Fonction ou méthode désignée par `name` à `position` : sa déclaration, sinon celle
+/// qu'un appel à cet endroit atteindrait.
+pub fn callable_at<'a>(symbols: &'a [SymbolInfo], name: &str, position: Position) -> Option<&'a SymbolInfo> {
+    let candidates: Vec<&SymbolInfo> = symbols.iter().filter(|s| is_callable(s) && s.name == name).collect();
+    let enclosing = enclosing_callable(symbols, position);
+    candidates
+        .iter()
+        .find(|s| contains(s.range, position))
+        .or_else(|| candidates.iter().rfind(|s| s.visible_at(position)))
+        .or_else(|| candidates.iter().find(|s| s.container.is_some() && s.container == enclosing.and_then(|e| e.container.clone())))
+        .or_else(|| candidates.first())
+        .copied()
+}
+
+/// Élément désigné par un `CallHierarchyItem` du client : même nom, même plage de nom.
+pub fn find_item<'a>(symbols: &'a [SymbolInfo], name: &str, selection: Range) -> Option<&'a SymbolInfo> {
+    symbols
+        .iter()
+        .filter(|s| is_callable(s) && s.name == name)
+        .find(|s| s.range == selection)
+        .or_else(|| symbols.iter().find(|s| is_callable(s) && s.name == name))
+}
+
+/// Appels faits dans le corps des fonctions et méthodes du document. Un appel `nom(...)`
+/// désigne la fonction `nom` visible à cet endroit ; `this.nom(...)` la méthode `nom` de la
+/// classe de l'appelant. Les autres appels de méthodes (classe du receveur inconnue) et
+/// ceux du niveau global, sans appelant, sont ignorés.
+pub fn calls<'a>(symbols: &'a [SymbolInfo], text: &str, index: &LineIndex) -> Vec<Call<'a>> {
+    let mut found = Vec::new();
+    for (offset, name) in references::identifiers(text) {
+        if !text[offset + name.len()..].trim_start().starts_with('(') {
+            continue;
+        }
+        let position = index.position(offset);
+        let Some(caller) = enclosing_callable(symbols, position) else {
+            continue;
+        };
+        // Nom d'une déclaration (`func nom(`) plutôt qu'appel
+        if symbols.iter().any(|s| is_callable(s) && s.range.start == position) {
+            continue;
+        }
+
+        let before = text[..offset].trim_end();
+        let callee = match before.strip_suffix('.') {
+            Some(receiver) if receiver.trim_end().ends_with("this") && caller.container.is_some() => symbols
+                .iter()
+                .find(|s| s.kind == CompletionItemKind::METHOD && s.name == name && s.container == caller.container),
+            Some(_) => None,
+            None => symbols.iter().filter(|s| s.kind == CompletionItemKind::FUNCTION && s.name == name).rfind(|s| s.visible_at(position)),
+        };
+        if let Some(callee) = callee {
+            let range = Range { start: position, end: index.position(offset + name.len()) };
+            found.push(Call { caller, callee, range });
+        }
+    }
+    found
+}
+
+/// Fonction ou méthode la plus intérieure qui contient `position`.
+fn enclosing_callable(symbols: &[SymbolInfo], position: Position) -> Option<&SymbolInfo> {
+    symbols
+        .iter()
+        .filter(|s| is_callable(s) && s.encloses(position))
+        .min_by_key(|s| (s.full_range.end.line - s.full_range.start.line, s.full_range.end.character))
+}
+
+fn contains(range: Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
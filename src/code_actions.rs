@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use crate::diagnostics::DiagnosticCode;
+use crate::line_index::LineIndex;
+
+/// Jeton attendu par le parser : "Expect ')' after arguments (Line 3)" ou ") (Line 3)".
+static EXPECTED_TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:Expect '(?P<quoted>[^']+)'|(?P<bare>[()\[\]{}:=]))[^(]*\((?:Line|Ligne) \d+").unwrap()
+});
+
+/// Jetons que la correction sait insérer.
+const INSERTABLE: &[&str] = &["(", ")", "[", "]", "{", "}", ":", "="];
+
+/// Corrections rapides des diagnostics de `diagnostics` qui recouvrent `range` : pour une
+/// erreur de syntaxe qui attend un jeton (parenthèse, accolade...), son insertion à la fin
+/// de la ligne signalée, avant l'accolade qui ouvre un bloc (`if (x {` devient `if (x) {`).
+/// Une accolade fermante manquante est ajoutée sur sa propre ligne.
+pub fn quick_fixes(uri: &Url, range: Range, diagnostics: &[Diagnostic], text: &str, index: &LineIndex) -> Vec<CodeActionOrCommand> {
+    diagnostics
+        .iter()
+        .filter(|d| overlaps(d.range, range))
+        .filter(|d| d.code == Some(NumberOrString::String(DiagnosticCode::Parse.as_str().to_string())))
+        .filter_map(|d| {
+            let token = expected_token(&d.message)?;
+            let position = insertion_point(d.range.end, token, text, index);
+            let new_text = if token == "}" { "\n}".to_string() } else { token.to_string() };
+            let edit = TextEdit { range: Range { start: position, end: position }, new_text };
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Insert missing '{}'", token),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![d.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(true),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+/// Action « Organiser les imports » : les `import` de premier niveau, triés par chemin et
+/// sans doublons, à la place du bloc qu'ils occupent. Les commentaires d'en-tête restent
+/// au-dessus du bloc, ceux qui précèdent un autre import le suivent ; les lignes vides du
+/// bloc disparaissent. None si le bloc est déjà en ordre, ou mêlé à d'autres instructions.
+pub fn organize_imports(uri: &Url, ast: &Value, text: &str, index: &LineIndex) -> Option<CodeActionOrCommand> {
+    // ["import", line, "chemin"] au premier niveau
+    let imports: Vec<(usize, &str)> = ast
+        .as_array()?
+        .iter()
+        .filter(|node| node.get(0).and_then(|v| v.as_str()) == Some("import"))
+        .filter_map(|node| Some((node.get(1)?.as_u64()?.checked_sub(1)? as usize, node.get(2)?.as_str()?)))
+        .collect();
+    let (first, last) = (imports.first()?.0, imports.last()?.0);
+    let lines: Vec<&str> = text.lines().collect();
+
+    let is_comment = |line: &str| line.trim_start().starts_with("//");
+
+    let mut entries: Vec<(&str, Vec<&str>)> = Vec::new();
+    let mut comments = Vec::new();
+    let mut remaining = imports.iter().peekable();
+    for (l, line) in lines.iter().enumerate().take(last + 1).skip(first) {
+        match remaining.peek() {
+            Some(&&(import_line, path)) if import_line == l => {
+                remaining.next();
+                // Deux imports sur une même ligne
+                if remaining.peek().is_some_and(|&&(next, _)| next == l) {
+                    return None;
+                }
+                match entries.iter_mut().find(|(p, _)| *p == path) {
+                    // Doublon : ses commentaires rejoignent ceux du premier import
+                    Some((_, kept)) => {
+                        let at = kept.len() - 1;
+                        for (i, comment) in comments.drain(..).enumerate() {
+                            kept.insert(at + i, comment);
+                        }
+                    },
+                    None => {
+                        comments.push(line.trim());
+                        entries.push((path, std::mem::take(&mut comments)));
+                    },
+                }
+            },
+            _ if is_comment(line) => comments.push(line.trim()),
+            _ if line.trim().is_empty() => {},
+            // Autre instruction au milieu des imports
+            _ => return None,
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let organized: String = entries.iter().flat_map(|(_, lines)| lines.iter().map(|line| format!("{}\n", line))).collect();
+    let end = Position { line: last as u32 + 1, character: 0 };
+    let range = Range { start: Position { line: first as u32, character: 0 }, end };
+    // Comparaison ligne à ligne : un dernier import sans saut de ligne final est en ordre
+    if text[index.offset(range.start)..index.offset(range.end)].lines().eq(organized.lines()) {
+        return None;
+    }
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Organize imports".to_string(),
+        kind: Some(CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![TextEdit { range, new_text: organized }])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+fn insertion_point(end: Position, token: &str, text: &str, index: &LineIndex) -> Position {
+    let line_start = index.offset(Position { line: end.line, character: 0 });
+    let before = text[line_start..index.offset(end)].trim_end();
+    match before.strip_suffix('{') {
+        Some(head) if token != "{" => index.position(line_start + head.trim_end().len()),
+        _ => end,
+    }
+}
+
+fn expected_token(message: &str) -> Option<&str> {
+    let captures = EXPECTED_TOKEN.captures(message)?;
+    let token = captures.name("quoted").or_else(|| captures.name("bare"))?.as_str();
+    INSERTABLE.contains(&token).then_some(token)
+}
+
+fn overlaps(a: Range, b: Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
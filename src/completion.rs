@@ -0,0 +1,190 @@
+use tower_lsp::lsp_types::CompletionItemKind;
+
+/// Mots-clés proposés à la complétion (et interdits comme nouveau nom lors d'un renommage).
+pub const KEYWORDS: &[&str] = &[
+    // Structures de contrôle
+    "if", "else", "while", "foreach", "return", "break", "continue",
+    "try", "catch", "throw",
+    // Déclarations
+    "var", "const", "func", "class", "interface", "namespace", "enum",
+    // Modificateurs & OOP
+    "new", "extends", "implements", "this", "super",
+    "public", "private", "protected", "static", "final",
+    "import", "from",
+    // Valeurs
+    "true", "false", "null",
+];
+
+/// Types acceptés dans les annotations (`var x: int`), vérifiés à l'exécution par la VM.
+pub const TYPES: &[&str] = &["int", "float", "string", "bool", "list", "dict", "func", "any"];
+
+/// Vrai si la fin de `prefix` (le texte du document jusqu'au curseur) se trouve
+/// dans une chaîne ("...", `...`) ou un commentaire (// ou /* */).
+pub fn in_string_or_comment(prefix: &str) -> bool {
+    #[derive(PartialEq)]
+    enum State { Code, String, Backtick, LineComment, BlockComment }
+
+    let mut state = State::Code;
+    let mut chars = prefix.chars().peekable();
+    while let Some(c) = chars.next() {
+        state = match state {
+            State::Code => match c {
+                '"' => State::String,
+                '`' => State::Backtick,
+                '/' if chars.peek() == Some(&'/') => { chars.next(); State::LineComment },
+                '/' if chars.peek() == Some(&'*') => { chars.next(); State::BlockComment },
+                _ => State::Code,
+            },
+            State::String => match c {
+                '\\' => { chars.next(); State::String },
+                '"' | '\n' => State::Code,
+                _ => State::String,
+            },
+            State::Backtick => if c == '`' { State::Code } else { State::Backtick },
+            State::LineComment => if c == '\n' { State::Code } else { State::LineComment },
+            State::BlockComment => {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    State::Code
+                } else {
+                    State::BlockComment
+                }
+            },
+        };
+    }
+
+    state != State::Code
+}
+
+/// Objet dont on complète un membre : l'identifiant qui précède `.` en fin de `prefix`,
+/// éventuellement suivi du début du nom du membre (`obj.`, `obj.mo`).
+pub fn member_receiver(prefix: &str) -> Option<&str> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let before_dot = prefix.trim_end_matches(is_ident).strip_suffix('.')?;
+    let receiver = &before_dot[before_dot.trim_end_matches(is_ident).len()..];
+    (!receiver.is_empty()).then_some(receiver)
+}
+
+/// Namespace dont on complète un membre : le chemin qualifié (`Foo.`, `Foo::Bar::`,
+/// éventuellement suivi du début du nom) qui précède le curseur, normalisé avec des `.`.
+pub fn namespace_path(prefix: &str) -> Option<String> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut rest = prefix.trim_end_matches(is_ident);
+    let mut segments = Vec::new();
+    while let Some(before) = rest.strip_suffix("::").or_else(|| rest.strip_suffix('.')) {
+        let segment = &before[before.trim_end_matches(is_ident).len()..];
+        if segment.is_empty() {
+            break;
+        }
+        segments.push(segment);
+        rest = &before[..before.len() - segment.len()];
+    }
+    if segments.is_empty() {
+        return None;
+    }
+    segments.reverse();
+    Some(segments.join("."))
+}
+
+/// Vrai si le curseur se trouve à la place d'un type, éventuellement déjà commencé :
+/// `var x: `, `func f(a, b: `, `func f() -> `.
+pub fn in_type_annotation(prefix: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let before = prefix.trim_end_matches(is_ident).trim_end();
+    if before.ends_with("->") {
+        return true;
+    }
+
+    // `nom:` précédé de `var`/`const`, ou d'un `(` / `,` de liste de paramètres
+    let Some(annotated) = before.strip_suffix(':').map(str::trim_end) else {
+        return false;
+    };
+    let rest = annotated.trim_end_matches(is_ident);
+    if rest.len() == annotated.len() {
+        return false;
+    }
+    let rest = rest.trim_end();
+    let keyword = &rest[rest.trim_end_matches(is_ident).len()..];
+    if keyword == "var" || keyword == "const" {
+        return true;
+    }
+    rest.ends_with(['(', ',']) && innermost_bracket(rest) == Some('(')
+}
+
+/// Seule sorte de nom valide au curseur, éventuellement déjà commencé : une classe après
+/// `new` ou `extends`, une interface dans la liste de `implements A, B, `. Un mot-clé
+/// écrit dans un commentaire ne compte pas.
+pub fn expected_kind(prefix: &str) -> Option<CompletionItemKind> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let last_word = |text: &str| text.len() - text.trim_end_matches(is_ident).len();
+
+    let before = prefix.trim_end_matches(is_ident);
+    let mut rest = before.trim_end();
+    if rest.len() == before.len() {
+        return None;
+    }
+    let mut in_list = false;
+    while let Some(list) = rest.strip_suffix(',') {
+        let list = list.trim_end();
+        let name = last_word(list);
+        if name == 0 {
+            return None;
+        }
+        rest = list[..list.len() - name].trim_end();
+        in_list = true;
+    }
+
+    let kind = match (&rest[rest.len() - last_word(rest)..], in_list) {
+        ("new" | "extends", false) => CompletionItemKind::CLASS,
+        ("implements", _) => CompletionItemKind::INTERFACE,
+        _ => return None,
+    };
+    (!in_string_or_comment(rest)).then_some(kind)
+}
+
+/// Dernier crochet, parenthèse ou accolade encore ouvert à la fin de `prefix`.
+fn innermost_bracket(prefix: &str) -> Option<char> {
+    let mut depth = 0;
+    for c in prefix.chars().rev() {
+        match c {
+            ')' | ']' | '}' => depth += 1,
+            '(' | '[' | '{' if depth > 0 => depth -= 1,
+            '(' | '[' | '{' => return Some(c),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Appel dont le curseur est dans la liste d'arguments : nom de la fonction appelée
+/// et index de l'argument en cours (nombre de virgules depuis la parenthèse ouvrante).
+/// On remonte `prefix` jusqu'à la première parenthèse non fermée, sans franchir un bloc.
+pub fn call_context(prefix: &str) -> Option<(&str, u32)> {
+    let mut depth = 0;
+    let mut commas = 0;
+    let mut in_string = false;
+
+    for (i, c) in prefix.char_indices().rev() {
+        if in_string {
+            in_string = c != '"';
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            ')' | ']' => depth += 1,
+            '[' if depth > 0 => depth -= 1,
+            '(' if depth > 0 => depth -= 1,
+            '(' => {
+                let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+                let before = prefix[..i].trim_end();
+                let callee = &before[before.trim_end_matches(is_ident).len()..];
+                return (!callee.is_empty()).then_some((callee, commas));
+            },
+            ',' if depth == 0 => commas += 1,
+            '{' | '}' | '[' => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
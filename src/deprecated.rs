@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use crate::references;
+
+/// Marqueur de dépréciation, en décorateur (`@deprecated`) ou dans un commentaire.
+const MARKER: &str = "@deprecated";
+
+/// Fonctions dépréciées du document : décorées par `@deprecated` (l'AST en fait
+/// `["set", line, "nom", null, ["call", ["get", "deprecated"], [lambda]]]`), ou dont la
+/// ligne précédant `func` est un commentaire qui contient `@deprecated`.
+pub fn deprecated_functions<'a>(ast: &'a Value, text: &str) -> HashSet<&'a str> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut found = HashSet::new();
+    collect(ast, &lines, &mut found);
+    found
+}
+
+fn collect<'a>(node: &'a Value, lines: &[&str], found: &mut HashSet<&'a str>) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            let name = arr.get(2).and_then(|v| v.as_str());
+            let line = arr.get(1).and_then(|v| v.as_u64()).map(|l| l.saturating_sub(1) as usize);
+            let deprecated = match cmd {
+                Some("set") => arr.get(4).is_some_and(is_decorator),
+                Some("function") => line.is_some_and(|l| l > 0 && is_marker_comment(lines.get(l - 1).copied().unwrap_or(""))),
+                _ => false,
+            };
+            if deprecated && let Some(name) = name {
+                found.insert(name);
+            }
+            for item in arr {
+                collect(item, lines, found);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                collect(item, lines, found);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// `["call", ["get", "deprecated"], [["lambda", ...]]]`
+fn is_decorator(expr: &Value) -> bool {
+    expr.get(0).and_then(|v| v.as_str()) == Some("call")
+        && expr.get(1) == Some(&json!(["get", "deprecated"]))
+        && expr.get(2).and_then(|args| args.get(0)).and_then(|f| f.get(0)).and_then(|v| v.as_str()) == Some("lambda")
+}
+
+fn is_marker_comment(line: &str) -> bool {
+    let line = line.trim_start();
+    (line.starts_with("//") || line.starts_with("/*") || line.starts_with('*')) && line.contains(MARKER)
+}
+
+/// Appels `nom(...)` de fonctions de `deprecated` dans `text` : offset d'octet et nom.
+/// Les déclarations (`func nom(`) et le décorateur lui-même ne comptent pas.
+pub fn deprecated_calls<'a>(text: &'a str, deprecated: &HashSet<&str>) -> Vec<(usize, &'a str)> {
+    let identifiers = references::identifiers(text);
+    identifiers
+        .iter()
+        .enumerate()
+        .filter(|&(i, &(offset, name))| {
+            deprecated.contains(name)
+                && text[offset + name.len()..].trim_start().starts_with('(')
+                && (i == 0 || identifiers[i - 1].1 != "func")
+        })
+        .map(|(_, &found)| found)
+        .collect()
+}
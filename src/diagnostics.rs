@@ -0,0 +1,1128 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use aegis_core::{compiler, loader};
+use regex::{Captures, Regex};
+use serde_json::Value;
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, Location, NumberOrString,
+    Position, Range, Url,
+};
+
+use crate::arity::{self, ArityMismatch};
+use crate::members::{self, UnknownMember};
+use crate::deprecated;
+use crate::flow;
+use crate::line_index::LineIndex;
+use crate::panics;
+use crate::references;
+use crate::selection;
+use crate::settings::{LintLevel, StageLevels};
+use crate::shadowing::Shadowing;
+use crate::symbols::{self, SymbolInfo};
+use crate::undefined;
+
+/// Formats de position des messages d'erreur, essayés dans l'ordre : "(Line X)",
+/// "[Ligne X]", "[Line X]", "at line X", chacun suivi d'une colonne facultative
+/// ("(Line X, Col Y)", "[Ligne X, Colonne Y]"...). Une erreur de bloc peut aussi couvrir
+/// plusieurs lignes : "(Line X..Y)", "(Line X, Col A..Y, Col B)".
+static LOCATIONS: LazyLock<[Regex; 3]> = LazyLock::new(|| {
+    const COLUMN: &str = r"(?:,\s*[[:alpha:]]+\s*(?P<column>\d+))?";
+    const SPAN: &str = r"(?:\.\.(?P<end_line>\d+)(?:,\s*[[:alpha:]]+\s*(?P<end_column>\d+))?)?";
+    [
+        Regex::new(&format!(r"\((?:Line|Ligne) (?P<line>\d+){COLUMN}{SPAN}\)")).unwrap(),
+        Regex::new(&format!(r"\[(?:Line|Ligne) (?P<line>\d+){COLUMN}\]")).unwrap(),
+        Regex::new(&format!(r"\bat line (?P<line>\d+){COLUMN}")).unwrap(),
+    ]
+});
+
+/// Préfixes des messages d'avertissement du compilateur ou du loader.
+const WARNING_PREFIXES: &[&str] = &["[Warning]", "[Avertissement]"];
+
+/// Mots-clés qui prolongent l'instruction précédente, même en début de ligne.
+const CONTINUATIONS: &[&str] = &["else", "catch", "case", "default"];
+
+/// Mots-clés de déclaration : en colonne 0, ils ouvrent une nouvelle instruction même
+/// si une accolade précédente n'a jamais été refermée.
+const DECLARATIONS: &[&str] = &["func", "class", "final", "var", "const", "namespace", "enum", "interface", "import"];
+
+/// Fragments des messages d'erreur du lexer, qui distinguent ses erreurs de celles du parser.
+const LEXER_MESSAGES: &[&str] = &[
+    "Lexer error",
+    "Unexpected char",
+    "Unterminated string",
+    "Unterminated block comment",
+    "Unclosed string interpolation",
+];
+
+/// Compteur des identifiants de résultat, partagé par les rapports de tous les documents.
+static NEXT_RESULT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Gravité d'un diagnostic, indépendante du protocole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Hint,
+}
+
+impl Severity {
+    fn to_lsp(self) -> DiagnosticSeverity {
+        match self {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Hint => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+/// Code stable d'un diagnostic, documenté sur aegis-lang.org : les actions de code
+/// s'appuient dessus plutôt que sur le texte du message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    // Caractère ou littéral invalide (lexer)
+    Lexer,
+    // Erreur de syntaxe du compilateur
+    Parse,
+    // Programme rejeté par le loader
+    Loader,
+    // Nom déjà déclaré dans la même portée
+    Duplicate,
+    // Nom lu sans être déclaré
+    Undefined,
+    // Fichier importé introuvable
+    Import,
+    // Appel avec un nombre d'arguments différent de celui des paramètres
+    Arity,
+    // Fichier importé qui ne compile pas
+    BrokenImport,
+    // Appel d'une méthode absente de la classe de l'instance
+    UnknownMember,
+    // Variable jamais lue
+    Unused,
+    // Instructions qui suivent un `return` dans le même bloc
+    Unreachable,
+    // Appel d'une fonction marquée `@deprecated`
+    Deprecated,
+    // Déclaration locale qui masque celle d'une portée englobante
+    Shadowing,
+    // Fonction typée dont un chemin se termine sans `return`
+    MissingReturn,
+    // Analyse désactivée (fichier trop volumineux)
+    Disabled,
+    // Panique du compilateur ou du loader
+    Internal,
+}
+
+impl DiagnosticCode {
+    const ALL: &[DiagnosticCode] = &[
+        DiagnosticCode::Lexer,
+        DiagnosticCode::Parse,
+        DiagnosticCode::Loader,
+        DiagnosticCode::Duplicate,
+        DiagnosticCode::Undefined,
+        DiagnosticCode::Import,
+        DiagnosticCode::Arity,
+        DiagnosticCode::BrokenImport,
+        DiagnosticCode::UnknownMember,
+        DiagnosticCode::Unused,
+        DiagnosticCode::Unreachable,
+        DiagnosticCode::Deprecated,
+        DiagnosticCode::Shadowing,
+        DiagnosticCode::MissingReturn,
+        DiagnosticCode::Disabled,
+        DiagnosticCode::Internal,
+    ];
+
+    /// Code d'après sa forme publiée ("AEG0300").
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.as_str() == code)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticCode::Lexer => "AEG0002",
+            DiagnosticCode::Parse => "AEG0001",
+            DiagnosticCode::Loader => "AEG0100",
+            DiagnosticCode::Duplicate => "AEG0200",
+            DiagnosticCode::Undefined => "AEG0201",
+            DiagnosticCode::Import => "AEG0202",
+            DiagnosticCode::BrokenImport => "AEG0203",
+            DiagnosticCode::Arity => "AEG0204",
+            DiagnosticCode::UnknownMember => "AEG0205",
+            DiagnosticCode::Unused => "AEG0300",
+            DiagnosticCode::Unreachable => "AEG0301",
+            DiagnosticCode::Deprecated => "AEG0302",
+            DiagnosticCode::Shadowing => "AEG0303",
+            DiagnosticCode::MissingReturn => "AEG0304",
+            DiagnosticCode::Disabled => "AEG0900",
+            DiagnosticCode::Internal => "AEG0901",
+        }
+    }
+
+    /// Étape qui a produit le diagnostic, affichée par l'éditeur.
+    pub fn source(self) -> &'static str {
+        match self {
+            DiagnosticCode::Lexer => "Aegis Lexer",
+            DiagnosticCode::Parse => "Aegis Parser",
+            DiagnosticCode::Loader => "Aegis Loader",
+            DiagnosticCode::Duplicate
+            | DiagnosticCode::Undefined
+            | DiagnosticCode::Import
+            | DiagnosticCode::BrokenImport
+            | DiagnosticCode::Arity
+            | DiagnosticCode::UnknownMember
+            | DiagnosticCode::Unused
+            | DiagnosticCode::Unreachable
+            | DiagnosticCode::Deprecated
+            | DiagnosticCode::Shadowing
+            | DiagnosticCode::MissingReturn => "Aegis Analyzer",
+            DiagnosticCode::Disabled | DiagnosticCode::Internal => "Aegis",
+        }
+    }
+
+    /// Code d'une erreur du compilateur, d'après son message : lexer ou parser.
+    pub fn of_compiler_error(msg: &str) -> Self {
+        if LEXER_MESSAGES.iter().any(|fragment| msg.contains(fragment)) {
+            DiagnosticCode::Lexer
+        } else {
+            DiagnosticCode::Parse
+        }
+    }
+
+    pub fn description(self) -> Option<CodeDescription> {
+        let href = Url::parse(&format!("https://aegis-lang.org/errors/{}", self.as_str())).ok()?;
+        Some(CodeDescription { href })
+    }
+}
+
+/// Erreur du compilateur ou du loader. aegis-lang ne la transmet que sous forme de
+/// message : `From<&str>` en retrouve la position, la gravité et l'étape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub message: String,
+    // Ligne et colonne, en base 0 ; None si le message n'en donne pas
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    // Fin d'une erreur sur plusieurs lignes, en base 0
+    pub end_line: Option<u32>,
+    pub end_column: Option<u32>,
+    // Longueur de l'élément fautif en caractères, si elle est connue
+    pub len: Option<u32>,
+    pub kind: DiagnosticCode,
+    pub severity: Severity,
+}
+
+impl From<&str> for CompileError {
+    /// Position d'après `parse_location`, avertissement si le message commence par
+    /// `[Warning]` / `[Avertissement]`, étape d'après `DiagnosticCode::of_compiler_error`.
+    fn from(msg: &str) -> Self {
+        let location = parse_location(msg);
+        let end = parse_end(msg);
+        let warning = WARNING_PREFIXES.iter().find_map(|prefix| msg.strip_prefix(prefix));
+        let (severity, message) = match warning {
+            Some(rest) => (Severity::Warning, rest.trim_start()),
+            None => (Severity::Error, msg),
+        };
+        CompileError {
+            message: message.to_string(),
+            line: location.map(|(line, _)| line),
+            column: location.and_then(|(_, column)| column),
+            end_line: end.map(|(line, _)| line),
+            end_column: end.and_then(|(_, column)| column),
+            len: None,
+            kind: DiagnosticCode::of_compiler_error(msg),
+            severity,
+        }
+    }
+}
+
+impl From<CompileError> for AegisDiagnostic {
+    /// Une erreur sans position est placée au tout début du document, signalée comme
+    /// telle (et marquée `unlocated`), plutôt que sur une ligne 1 qui n'y est pour rien.
+    fn from(error: CompileError) -> Self {
+        let message = match error.line {
+            Some(_) => error.message,
+            None => format!("{} (position not reported by the {})", error.message, error.kind.source()),
+        };
+
+        AegisDiagnostic {
+            severity: error.severity,
+            line: error.line.unwrap_or_default(),
+            column: error.column,
+            length: error.len,
+            end_line: error.end_line,
+            end_column: error.end_column,
+            message,
+            code: Some(error.kind),
+            related: Vec::new(),
+            tags: Vec::new(),
+            unlocated: error.line.is_none(),
+        }
+    }
+}
+
+/// Diagnostic produit par la validation, avant conversion en plage LSP.
+#[derive(Debug, Clone)]
+pub struct AegisDiagnostic {
+    pub severity: Severity,
+    // Ligne et colonne éventuelle, en base 0
+    pub line: u32,
+    pub column: Option<u32>,
+    // Nombre de caractères soulignés à partir de la colonne (par défaut : le token qui y commence)
+    pub length: Option<u32>,
+    // Dernière ligne couverte, pour un diagnostic sur plusieurs lignes, et colonne de fin
+    // éventuelle (par défaut : la fin de cette ligne)
+    pub end_line: Option<u32>,
+    pub end_column: Option<u32>,
+    pub message: String,
+    pub code: Option<DiagnosticCode>,
+    // Autres emplacements du document liés au diagnostic, avec leur explication
+    pub related: Vec<(Range, String)>,
+    // Code inutile (estompé) ou déprécié (barré) pour l'éditeur
+    pub tags: Vec<DiagnosticTag>,
+    // Message sans position : diagnostic vide au début du document
+    pub unlocated: bool,
+}
+
+impl AegisDiagnostic {
+    /// Diagnostic LSP : souligne le token à la colonne connue, sinon les lignes couvertes
+    /// hors indentation. Une erreur signalée après la fin du texte (fin de fichier
+    /// inattendue) est ramenée sur sa dernière ligne non vide.
+    pub fn to_lsp(&self, uri: &Url, text: &str, index: &LineIndex) -> Diagnostic {
+        let mut line_start = index.offset(Position { line: self.line, character: 0 });
+        let mut column = self.column;
+        let content_end = text[..line_start].trim_end().len();
+        if text[line_start..].trim().is_empty() && content_end > 0 {
+            line_start = index.offset(Position { line: index.position(content_end).line, character: 0 });
+            column = None;
+        }
+        let line_text = text[line_start..].lines().next().unwrap_or("");
+
+        // Offsets d'octets dans la ligne
+        let (start, end) = match column {
+            // Colonne connue : on souligne `length` caractères, ou le token qui commence à cette position (au moins un caractère)
+            Some(col) => {
+                let start = line_text.char_indices().nth(col as usize).map_or(line_text.len(), |(i, _)| i);
+                let rest = &line_text[start..];
+                let token_len = match self.length {
+                    Some(len) => rest.char_indices().nth(len as usize).map_or(rest.len(), |(i, _)| i),
+                    None => rest
+                        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len()),
+                };
+                let token_len = if token_len == 0 { rest.chars().next().map_or(0, char::len_utf8) } else { token_len };
+                (start, start + token_len)
+            },
+            // Sinon : du premier caractère non blanc jusqu'à la fin de la ligne
+            None => {
+                let start = line_text.len() - line_text.trim_start().len();
+                (start, line_text.trim_end().len().max(start))
+            }
+        };
+
+        // Plusieurs lignes : jusqu'à la colonne de fin, ou la fin de la dernière hors blancs
+        // finaux ; une fin au-delà du texte est ramenée à la fin de son contenu
+        let end = match self.end_line.filter(|&last| last > index.position(line_start).line) {
+            Some(last) if last >= index.position(text.len()).line => text.trim_end().len(),
+            Some(last) => {
+                let last_start = index.offset(Position { line: last, character: 0 });
+                let last_text = text[last_start..].lines().next().unwrap_or("");
+                match self.end_column {
+                    Some(col) => last_start + last_text.char_indices().nth(col as usize).map_or(last_text.len(), |(i, _)| i),
+                    None => last_start + last_text.trim_end().len(),
+                }
+            },
+            None => line_start + end,
+        }
+        .max(line_start + start);
+
+        let range = if self.unlocated {
+            Range::default()
+        } else {
+            Range { start: index.position(line_start + start), end: index.position(end) }
+        };
+
+        Diagnostic {
+            range,
+            severity: Some(self.severity.to_lsp()),
+            code: self.code.map(|c| NumberOrString::String(c.as_str().to_string())),
+            code_description: self.code.and_then(DiagnosticCode::description),
+            source: Some(self.code.map_or("Aegis", DiagnosticCode::source).to_string()),
+            message: self.message.clone(),
+            tags: (!self.tags.is_empty()).then(|| self.tags.clone()),
+            related_information: (!self.related.is_empty()).then(|| {
+                self.related
+                    .iter()
+                    .map(|(range, message)| DiagnosticRelatedInformation {
+                        location: Location { uri: uri.clone(), range: *range },
+                        message: message.clone(),
+                    })
+                    .collect()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// Diagnostics calculés pour une version d'un document (None : contenu du disque).
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub version: Option<i32>,
+    // Identifiant renvoyé au client, qui le rappelle dans `previousResultId`
+    pub result_id: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    /// Nouveau rapport, avec un identifiant jamais attribué.
+    pub fn new(version: Option<i32>, diagnostics: Vec<Diagnostic>) -> Self {
+        let result_id = NEXT_RESULT_ID.fetch_add(1, Ordering::Relaxed).to_string();
+        Report { version, result_id, diagnostics }
+    }
+}
+
+/// Diagnostics du texte d'après le résultat de sa compilation : erreurs du loader,
+/// redéfinitions (parmi `symbols`, extraits de l'AST), variables jamais lues, code
+/// inaccessible, noms non déclarés et appels au mauvais nombre d'arguments si elle a réussi,
+/// sinon erreurs de syntaxe de tout le document.
+/// `external` contient les noms apportés par les imports ; None si un import n'a pas pu
+/// être résolu, auquel cas les noms non déclarés ne sont pas signalés. `imported` contient
+/// les symboles des fichiers importés.
+pub fn check(
+    text: &str,
+    compiled: &Result<Value, String>,
+    symbols: &[SymbolInfo],
+    external: Option<&HashSet<String>>,
+    imported: &[SymbolInfo],
+    index: &LineIndex,
+) -> Vec<AegisDiagnostic> {
+    match compiled {
+        Ok(ast) => {
+            let mut errors = match loader::parse_block(ast) {
+                Ok(_) => Vec::new(),
+                Err(e) => vec![loader_error(ast, &e)],
+            };
+            errors.extend(symbols::duplicates(symbols).into_iter().map(|(symbol, first)| duplicate(symbol, first, text, index)));
+            let reads = symbols::reads(ast);
+            errors.extend(symbols::unused_variables(symbols, &reads).into_iter().map(|symbol| unused(symbol, text, index)));
+            errors.extend(flow::unreachable(ast, text).into_iter().map(|(first, last)| unreachable(first, last)));
+            errors.extend(flow::missing_returns(ast).into_iter().filter_map(|(line, name)| missing_return(name, line, text, index)));
+            let deprecated = deprecated::deprecated_functions(ast, text);
+            errors.extend(
+                deprecated::deprecated_calls(text, &deprecated)
+                    .into_iter()
+                    .map(|(offset, name)| deprecated_call(name, offset, text, index)),
+            );
+            errors.extend(arity::arity_mismatches(ast, imported).iter().filter_map(|call| arity_mismatch(call, text, index)));
+            errors.extend(members::unknown_members(ast).iter().filter_map(|call| unknown_member(call, text, index)));
+            if let Some(external) = external {
+                errors.extend(
+                    undefined::undefined_names(ast, external)
+                        .into_iter()
+                        .filter_map(|(line, name)| undefined_name(name, line, text, index)),
+                );
+            }
+            errors
+        },
+        Err(e) => compile_errors(text, e).into_iter().map(AegisDiagnostic::from).collect(),
+    }
+}
+
+/// Erreur du loader. Ses messages ("Invalid class name", "Instruction inconnue: x"...) ne
+/// donnent en général pas de position : on prend alors la ligne de l'instruction la plus
+/// profonde que le loader rejette.
+fn loader_error(ast: &Value, msg: &str) -> AegisDiagnostic {
+    let mut error = CompileError::from(msg);
+    error.kind = DiagnosticCode::Loader;
+    if error.line.is_none() {
+        error.line = rejected_line(ast, 0);
+    }
+    error.into()
+}
+
+/// Ligne (base 0) de l'instruction la plus profonde de `node` rejetée par le loader.
+/// Une instruction est un nœud ["cmd", ligne, ...] dont la ligne ne précède pas celle de
+/// l'instruction englobante `min_line` (ce qui écarte les expressions comme ["+", 1, 2]).
+/// Le loader n'est sollicité que sur le chemin des instructions rejetées : une instruction
+/// acceptée n'est pas explorée, et chaque niveau s'arrête à la première rejetée.
+fn rejected_line(node: &Value, min_line: u64) -> Option<u32> {
+    match node {
+        Value::Array(arr) => {
+            if let (Some(Value::String(_)), Some(line)) = (arr.first(), arr.get(1).and_then(Value::as_u64))
+                && line >= min_line {
+                // Le loader peut aussi paniquer sur un nœud inattendu : c'est un rejet
+                if let Ok(Ok(_)) = panics::catch(|| loader::parse_statement_json(node)) {
+                    return None;
+                }
+                let deeper = arr[2..].iter().find_map(|child| rejected_line(child, line));
+                return deeper.or(Some(line.saturating_sub(1) as u32));
+            }
+            arr.iter().find_map(|child| rejected_line(child, min_line))
+        },
+        Value::Object(map) => map.values().find_map(|child| rejected_line(child, min_line)),
+        _ => None,
+    }
+}
+
+/// Redéfinition de `symbol`, soulignée sur son nom et reliée à la première définition.
+pub fn duplicate(symbol: &SymbolInfo, first: &SymbolInfo, text: &str, index: &LineIndex) -> AegisDiagnostic {
+    AegisDiagnostic {
+        severity: Severity::Error,
+        line: symbol.range.start.line,
+        column: Some(char_column(symbol.range.start, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("'{}' is already defined", symbol.name),
+        code: Some(DiagnosticCode::Duplicate),
+        related: vec![(first.range, "first defined here".to_string())],
+        tags: Vec::new(),
+        unlocated: false,
+    }
+}
+
+/// Variable `symbol` jamais lue, soulignée sur sa déclaration.
+fn unused(symbol: &SymbolInfo, text: &str, index: &LineIndex) -> AegisDiagnostic {
+    AegisDiagnostic {
+        severity: Severity::Warning,
+        line: symbol.range.start.line,
+        column: Some(char_column(symbol.range.start, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("Variable '{}' is never read", symbol.name),
+        code: Some(DiagnosticCode::Unused),
+        related: Vec::new(),
+        tags: vec![DiagnosticTag::UNNECESSARY],
+        unlocated: false,
+    }
+}
+
+/// Instructions inaccessibles des lignes `first` à `last`, affichées estompées.
+fn unreachable(first: u32, last: u32) -> AegisDiagnostic {
+    AegisDiagnostic {
+        severity: Severity::Warning,
+        line: first,
+        column: None,
+        length: None,
+        end_line: Some(last),
+        end_column: None,
+        message: "Unreachable code".to_string(),
+        code: Some(DiagnosticCode::Unreachable),
+        related: Vec::new(),
+        tags: vec![DiagnosticTag::UNNECESSARY],
+        unlocated: false,
+    }
+}
+
+/// Fonction `name` (déclarée ligne `line`) dont un chemin se termine sans `return`,
+/// soulignée sur son nom.
+fn missing_return(name: &str, line: u32, text: &str, index: &LineIndex) -> Option<AegisDiagnostic> {
+    let line_start = index.offset(Position { line, character: 0 });
+    let offset = line_start + references::identifier_offsets(&text[line_start..], name).first()?;
+    let position = index.position(offset);
+
+    Some(AegisDiagnostic {
+        severity: Severity::Warning,
+        line: position.line,
+        column: Some(char_column(position, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("'{}' does not return a value on every path", name),
+        code: Some(DiagnosticCode::MissingReturn),
+        related: Vec::new(),
+        tags: Vec::new(),
+        unlocated: false,
+    })
+}
+
+/// Appel de la fonction dépréciée `name` (à l'offset `offset`), affiché barré.
+fn deprecated_call(name: &str, offset: usize, text: &str, index: &LineIndex) -> AegisDiagnostic {
+    let position = index.position(offset);
+    AegisDiagnostic {
+        severity: Severity::Warning,
+        line: position.line,
+        column: Some(char_column(position, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("'{}' is deprecated", name),
+        code: Some(DiagnosticCode::Deprecated),
+        related: Vec::new(),
+        tags: vec![DiagnosticTag::DEPRECATED],
+        unlocated: false,
+    }
+}
+
+/// Appel `call` au mauvais nombre d'arguments, souligné sur le nom de la fonction, ou sur
+/// la liste d'arguments d'un `new`.
+fn arity_mismatch(call: &ArityMismatch, text: &str, index: &LineIndex) -> Option<AegisDiagnostic> {
+    if call.constructor {
+        return constructor_mismatch(call, text, index);
+    }
+    let line_start = index.offset(Position { line: call.line, character: 0 });
+    let rest = &text[line_start..];
+    let offset = references::identifier_offsets(rest, call.name)
+        .into_iter()
+        .filter(|&offset| rest[offset + call.name.len()..].trim_start().starts_with('('))
+        .nth(call.occurrence)?;
+    let position = index.position(line_start + offset);
+
+    let plural = if call.expected == 1 { "argument" } else { "arguments" };
+    Some(AegisDiagnostic {
+        severity: Severity::Error,
+        line: position.line,
+        column: Some(char_column(position, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("'{}' expects {} {}, found {}", call.name, call.expected, plural, call.found),
+        code: Some(DiagnosticCode::Arity),
+        related: Vec::new(),
+        tags: Vec::new(),
+        unlocated: false,
+    })
+}
+
+/// `new Classe(...)` au mauvais nombre d'arguments, souligné de la parenthèse ouvrante à la
+/// fermante (jusqu'à la fin de la ligne si elle manque).
+fn constructor_mismatch(call: &ArityMismatch, text: &str, index: &LineIndex) -> Option<AegisDiagnostic> {
+    let line_start = index.offset(Position { line: call.line, character: 0 });
+    let rest = &text[line_start..];
+    let offset = references::identifier_offsets(rest, call.name)
+        .into_iter()
+        .filter(|&offset| rest[..offset].trim_end().ends_with("new"))
+        .filter(|&offset| rest[offset + call.name.len()..].trim_start().starts_with('('))
+        .nth(call.occurrence)?;
+    let after = &rest[offset + call.name.len()..];
+    let open = line_start + offset + call.name.len() + (after.len() - after.trim_start().len());
+    let close = selection::delimiters(text)
+        .into_iter()
+        .find(|&(start, _)| start == open)
+        .map_or_else(|| open + text[open..].find('\n').unwrap_or(text.len() - open), |(_, end)| end);
+    let (start, end) = (index.position(open), index.position(close));
+
+    let plural = if call.expected == 1 { "argument" } else { "arguments" };
+    let same_line = start.line == end.line;
+    Some(AegisDiagnostic {
+        severity: Severity::Error,
+        line: start.line,
+        column: Some(char_column(start, text, index)),
+        length: same_line.then(|| text[open..close].chars().count() as u32),
+        end_line: (!same_line).then_some(end.line),
+        end_column: (!same_line).then(|| char_column(end, text, index)),
+        message: format!("'{}' constructor expects {} {}, found {}", call.name, call.expected, plural, call.found),
+        code: Some(DiagnosticCode::Arity),
+        related: Vec::new(),
+        tags: Vec::new(),
+        unlocated: false,
+    })
+}
+
+/// Appel d'une méthode inconnue de la classe, souligné sur le nom de la méthode.
+fn unknown_member(call: &UnknownMember, text: &str, index: &LineIndex) -> Option<AegisDiagnostic> {
+    let line_start = index.offset(Position { line: call.line, character: 0 });
+    let rest = &text[line_start..];
+    let offset = references::identifier_offsets(rest, call.name)
+        .into_iter()
+        .filter(|&offset| rest[..offset].trim_end().ends_with('.'))
+        .nth(call.occurrence)?;
+    let position = index.position(line_start + offset);
+
+    let mut message = format!("'{}' is not a member of class '{}'", call.name, call.class);
+    if !call.suggestions.is_empty() {
+        let names: Vec<String> = call.suggestions.iter().map(|name| format!("'{}'", name)).collect();
+        message = format!("{}; did you mean {}?", message, names.join(", "));
+    }
+    Some(AegisDiagnostic {
+        severity: Severity::Error,
+        line: position.line,
+        column: Some(char_column(position, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message,
+        code: Some(DiagnosticCode::UnknownMember),
+        related: Vec::new(),
+        tags: Vec::new(),
+        unlocated: false,
+    })
+}
+
+/// Déclaration qui en masque une autre, soulignée sur son nom et reliée à la déclaration masquée.
+pub fn shadowing(shadowing: &Shadowing, text: &str, index: &LineIndex) -> Option<AegisDiagnostic> {
+    let name_at = |line: u32| {
+        let line_start = index.offset(Position { line, character: 0 });
+        Some(line_start + references::identifier_offsets(&text[line_start..], shadowing.name).first()?)
+    };
+    let position = index.position(name_at(shadowing.line)?);
+    let shadowed = name_at(shadowing.shadowed_line)?;
+    let shadowed = Range { start: index.position(shadowed), end: index.position(shadowed + shadowing.name.len()) };
+
+    Some(AegisDiagnostic {
+        severity: Severity::Warning,
+        line: position.line,
+        column: Some(char_column(position, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("'{}' shadows an outer declaration", shadowing.name),
+        code: Some(DiagnosticCode::Shadowing),
+        related: vec![(shadowed, "shadowed declaration".to_string())],
+        tags: Vec::new(),
+        unlocated: false,
+    })
+}
+
+/// Nom `name` non déclaré, souligné à sa première occurrence depuis le début de
+/// l'instruction qui le lit (ligne `line`, base 0).
+fn undefined_name(name: &str, line: u32, text: &str, index: &LineIndex) -> Option<AegisDiagnostic> {
+    let line_start = index.offset(Position { line, character: 0 });
+    let offset = line_start + references::identifier_offsets(&text[line_start..], name).first()?;
+    let position = index.position(offset);
+
+    Some(AegisDiagnostic {
+        severity: Severity::Error,
+        line: position.line,
+        column: Some(char_column(position, text, index)),
+        length: None,
+        end_line: None,
+        end_column: None,
+        message: format!("'{}' is not defined", name),
+        code: Some(DiagnosticCode::Undefined),
+        related: Vec::new(),
+        tags: Vec::new(),
+        unlocated: false,
+    })
+}
+
+/// Import de `path` (ligne `line`, base 0) qui ne correspond à aucun fichier, souligné sur
+/// son chemin. Dans un document jamais enregistré, les chemins relatifs n'ont pas de
+/// dossier de référence : simple indication.
+pub fn unresolved_import(path: &str, line: u32, saved: bool, text: &str, index: &LineIndex) -> AegisDiagnostic {
+    let (severity, message) = if saved {
+        (Severity::Error, format!("Cannot find imported file '{}'", path))
+    } else {
+        (Severity::Hint, "Cannot resolve imports for an unsaved file".to_string())
+    };
+    import_diagnostic(path, line, severity, message, DiagnosticCode::Import, text, index)
+}
+
+/// Import de `path` dont le fichier ne compile pas, avec sa première erreur.
+pub fn broken_import(path: &str, line: u32, error: &CompileError, text: &str, index: &LineIndex) -> AegisDiagnostic {
+    let message = format!("Imported file '{}' has errors: {}", path, error.message);
+    import_diagnostic(path, line, Severity::Warning, message, DiagnosticCode::BrokenImport, text, index)
+}
+
+fn import_diagnostic(
+    path: &str,
+    line: u32,
+    severity: Severity,
+    message: String,
+    code: DiagnosticCode,
+    text: &str,
+    index: &LineIndex,
+) -> AegisDiagnostic {
+    let line_start = index.offset(Position { line, character: 0 });
+    let line_text = text[line_start..].lines().next().unwrap_or("");
+    // Le chemin entre guillemets ; à défaut, toute la ligne
+    let column = line_text.find(&format!("\"{}\"", path)).map(|i| line_text[..i].chars().count() as u32);
+    AegisDiagnostic {
+        severity,
+        line,
+        column,
+        length: column.map(|_| path.chars().count() as u32 + 2),
+        end_line: None,
+        end_column: None,
+        message,
+        code: Some(code),
+        related: Vec::new(),
+        tags: Vec::new(),
+        unlocated: false,
+    }
+}
+
+/// Colonne de `position` en caractères, comme celles des messages du compilateur.
+fn char_column(position: Position, text: &str, index: &LineIndex) -> u32 {
+    let line_start = index.offset(Position { line: position.line, character: 0 });
+    text[line_start..index.offset(position)].chars().count() as u32
+}
+
+/// Extrait la ligne (base 0) et la colonne éventuelle (base 0) d'un message d'erreur,
+/// d'après le premier format de `LOCATIONS` reconnu.
+pub fn parse_location(msg: &str) -> Option<(u32, Option<u32>)> {
+    let captures = find_location(msg)?;
+    let line = captures["line"].parse::<u32>().ok()?;
+    let column = captures.name("column").and_then(|c| c.as_str().parse::<u32>().ok());
+    Some((line.saturating_sub(1), column.map(|c| c.saturating_sub(1))))
+}
+
+/// Fin (ligne et colonne éventuelle, base 0) d'une position sur plusieurs lignes.
+pub fn parse_end(msg: &str) -> Option<(u32, Option<u32>)> {
+    let captures = find_location(msg)?;
+    let line = captures.name("end_line")?.as_str().parse::<u32>().ok()?;
+    let column = captures.name("end_column").and_then(|c| c.as_str().parse::<u32>().ok());
+    Some((line.saturating_sub(1), column.map(|c| c.saturating_sub(1))))
+}
+
+fn find_location(msg: &str) -> Option<Captures<'_>> {
+    LOCATIONS.iter().find_map(|pattern| pattern.captures(msg))
+}
+
+/// Découpe un rapport d'erreurs du compilateur en messages individuels.
+/// Le compilateur actuel s'arrête à la première erreur, mais un rapport
+/// multi-lignes (une erreur par ligne) produit un diagnostic par erreur.
+pub fn split_errors(report: &str) -> Vec<String> {
+    let errors: Vec<String> = report
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect();
+
+    if errors.is_empty() { vec![report.to_string()] } else { errors }
+}
+
+/// Gravités choisies par l'utilisateur : celle du code dans `levels`, sinon celle de son
+/// étape dans `stages` ; `off` retire le diagnostic. Les erreurs de syntaxe (lexer,
+/// parser) et les erreurs internes restent au moins des avertissements.
+pub fn apply_levels(mut diagnostics: Vec<Diagnostic>, levels: &HashMap<String, LintLevel>, stages: &StageLevels) -> Vec<Diagnostic> {
+    const SYNTAX: &[DiagnosticCode] = &[DiagnosticCode::Lexer, DiagnosticCode::Parse, DiagnosticCode::Internal];
+    if levels.is_empty() && *stages == StageLevels::default() {
+        return diagnostics;
+    }
+
+    diagnostics.retain_mut(|diagnostic| {
+        let Some(NumberOrString::String(code)) = &diagnostic.code else {
+            return true;
+        };
+        let parsed = DiagnosticCode::parse(code);
+        let stage = parsed.and_then(|parsed| match parsed {
+            DiagnosticCode::Lexer => stages.lexer,
+            DiagnosticCode::Parse => stages.parser,
+            DiagnosticCode::Loader => stages.loader,
+            DiagnosticCode::Disabled | DiagnosticCode::Internal => None,
+            _ => stages.analyzer,
+        });
+        let Some(level) = levels.get(code).copied().or(stage) else {
+            return true;
+        };
+        let syntax = parsed.is_some_and(|parsed| SYNTAX.contains(&parsed));
+        diagnostic.severity = match level {
+            LintLevel::Error => Some(DiagnosticSeverity::ERROR),
+            LintLevel::Warning => Some(DiagnosticSeverity::WARNING),
+            _ if syntax => Some(DiagnosticSeverity::WARNING),
+            LintLevel::Info => Some(DiagnosticSeverity::INFORMATION),
+            LintLevel::Hint => Some(DiagnosticSeverity::HINT),
+            LintLevel::Off => return false,
+        };
+        true
+    });
+    diagnostics
+}
+
+/// Retire les doublons que produisent parfois le loader et le compilateur pour un même
+/// problème : à plage, code et message normalisé identiques, seul le plus grave reste.
+/// Les messages identiques restants d'une même ligne n'en font plus qu'un, suffixé de
+/// "(reported N times)".
+pub fn dedupe(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    let severity = |d: &Diagnostic| d.severity.unwrap_or(DiagnosticSeverity::ERROR);
+
+    // Range et NumberOrString ne sont pas hachables
+    let key = |d: &Diagnostic, message: &str| {
+        let code = match &d.code {
+            Some(NumberOrString::String(code)) => code.clone(),
+            Some(NumberOrString::Number(code)) => code.to_string(),
+            None => String::new(),
+        };
+        let Range { start, end } = d.range;
+        ((start.line, start.character, end.line, end.character), code, message.to_string())
+    };
+
+    let mut unique: Vec<(String, Diagnostic)> = Vec::new();
+    let mut seen: HashMap<_, usize> = HashMap::new();
+    for diagnostic in diagnostics {
+        let message = normalize_message(&diagnostic.message);
+        match seen.get(&key(&diagnostic, &message)) {
+            Some(&i) => {
+                if severity(&diagnostic) < severity(&unique[i].1) {
+                    unique[i].1 = diagnostic;
+                }
+            },
+            None => {
+                seen.insert(key(&diagnostic, &message), unique.len());
+                unique.push((message, diagnostic));
+            },
+        }
+    }
+
+    // Même ligne, même message : le plus grave, à la place du premier
+    let mut collapsed: Vec<(Diagnostic, usize)> = Vec::new();
+    let mut lines: HashMap<(u32, String), usize> = HashMap::new();
+    for (message, diagnostic) in unique {
+        match lines.get(&(diagnostic.range.start.line, message.clone())) {
+            Some(&i) => {
+                let (kept, count) = &mut collapsed[i];
+                *count += 1;
+                if severity(&diagnostic) < severity(kept) {
+                    *kept = diagnostic;
+                }
+            },
+            None => {
+                lines.insert((diagnostic.range.start.line, message), collapsed.len());
+                collapsed.push((diagnostic, 1));
+            },
+        }
+    }
+    collapsed
+        .into_iter()
+        .map(|(mut diagnostic, count)| {
+            if count > 1 {
+                diagnostic.message = format!("{} (reported {} times)", diagnostic.message, count);
+            }
+            diagnostic
+        })
+        .collect()
+}
+
+/// Message sans position, préfixe d'avertissement ni différences de casse ou d'espaces.
+fn normalize_message(message: &str) -> String {
+    let mut message = message.to_string();
+    for pattern in LOCATIONS.iter() {
+        message = pattern.replace_all(&message, "").into_owned();
+    }
+    let message = WARNING_PREFIXES.iter().fold(message.trim(), |m, prefix| m.strip_prefix(prefix).unwrap_or(m));
+    message
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', ':'])
+        .to_lowercase()
+}
+
+/// Garde au plus `max` diagnostics, les erreurs avant les avertissements, dans l'ordre du
+/// document ; les autres sont résumés par un dernier diagnostic d'information placé sur
+/// le dernier gardé.
+pub fn truncate(mut diagnostics: Vec<Diagnostic>, max: usize) -> Vec<Diagnostic> {
+    if diagnostics.len() <= max {
+        return diagnostics;
+    }
+    let hidden = diagnostics.len() - max;
+
+    // Tri stable : l'ordre du document est conservé à gravité égale
+    let mut ranked: Vec<usize> = (0..diagnostics.len()).collect();
+    ranked.sort_by_key(|&i| diagnostics[i].severity.unwrap_or(DiagnosticSeverity::ERROR));
+    let mut kept = vec![false; diagnostics.len()];
+    for &i in &ranked[..max] {
+        kept[i] = true;
+    }
+    let mut kept = kept.into_iter();
+    diagnostics.retain(|_| kept.next().unwrap_or(false));
+
+    let range = diagnostics.last().map(|d| d.range).unwrap_or_default();
+    diagnostics.push(Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        source: Some("Aegis".to_string()),
+        message: format!("{} additional problems not shown", hidden),
+        ..Default::default()
+    });
+    diagnostics
+}
+
+/// Erreurs de syntaxe de tout le document, alors que le compilateur s'arrête à la première
+/// (`report`). Chaque instruction de premier niveau est recompilée séparément et ses
+/// erreurs sont ramenées aux lignes du document ; une seule erreur est gardée par ligne
+/// pour écarter les erreurs en cascade.
+pub fn compile_errors(text: &str, report: &str) -> Vec<CompileError> {
+    let mut errors = Vec::new();
+    for (start, chunk) in statements(text) {
+        // Sans les lignes vides finales, l'erreur de fin de texte reste sur la dernière ligne du morceau
+        let chunk = chunk.trim_end();
+        // Le lexer panique sur certaines entrées : on ignore simplement le morceau
+        let Ok(Err(e)) = panics::catch(|| compiler::compile(chunk)) else {
+            continue;
+        };
+        errors.extend(split_errors(&e).iter().map(|e| CompileError::from(shift_line(e, start).as_str())));
+    }
+
+    // Découpage trompé (construction valide coupée en deux...) : on s'en tient au rapport
+    if errors.is_empty() {
+        return split_errors(report).iter().map(|e| CompileError::from(e.as_str())).collect();
+    }
+
+    let mut lines = HashSet::new();
+    errors.retain(|e| lines.insert(e.line));
+    errors
+}
+
+/// Décale de `offset` la ligne (et la ligne de fin) du message d'un morceau compilé seul ;
+/// un message sans position reçoit celle du début du morceau.
+fn shift_line(msg: &str, offset: usize) -> String {
+    let Some(captures) = find_location(msg) else {
+        return format!("{} (Line {})", msg, offset + 1);
+    };
+    let mut shifted = msg.to_string();
+    // De la fin vers le début, pour que les positions restent valides
+    for group in ["end_line", "line"] {
+        if let Some(line) = captures.name(group)
+            && let Ok(n) = line.as_str().parse::<usize>() {
+            shifted.replace_range(line.range(), &(n + offset).to_string());
+        }
+    }
+    shifted
+}
+
+/// Instructions de premier niveau du texte : (ligne de début en base 0, texte).
+/// Une instruction commence en colonne 0, hors chaîne et commentaire, au niveau 0 des
+/// accolades ou par un mot-clé de déclaration.
+fn statements(text: &str) -> Vec<(usize, &str)> {
+    let mut starts = Vec::new();
+    let mut depth: i32 = 0;
+    let mut quote = None;
+    let mut in_block_comment = false;
+    let mut offset = 0;
+
+    for (l, line) in text.split_inclusive('\n').enumerate() {
+        let first_word = line
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or("");
+        let opens = line.starts_with(|c: char| !c.is_whitespace() && c != '}')
+            && !line.starts_with("//")
+            && !CONTINUATIONS.contains(&first_word)
+            && (depth <= 0 || DECLARATIONS.contains(&first_word) || line.starts_with('@'));
+        if starts.is_empty() || (quote.is_none() && !in_block_comment && opens) {
+            starts.push((l, offset));
+            depth = 0;
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            if let Some(q) = quote {
+                match c {
+                    '\\' => { chars.next(); },
+                    c if c == q => quote = None,
+                    _ => {},
+                }
+                continue;
+            }
+            match c {
+                '"' | '`' => quote = Some(c),
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    in_block_comment = true;
+                },
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {},
+            }
+        }
+        offset += line.len();
+    }
+
+    let mut chunks = Vec::with_capacity(starts.len());
+    for (i, &(line, start)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).map_or(text.len(), |&(_, next)| next);
+        chunks.push((line, &text[start..end]));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn rejected_line_finds_the_deepest_rejected_statement() {
+        let cases = [
+            // Programme valide : rien à signaler
+            (json!([["print", 1, "a"], ["set", 2, "x", null, 3]]), None),
+            // Instruction inconnue : Err du loader
+            (json!([["print", 1, "a"], ["bogus", 2]]), Some(1)),
+            // Argument manquant : le loader panique sur l'index
+            (json!([["print", 1, "a"], ["print", 2]]), Some(1)),
+            // Seule la première rejetée d'un niveau compte
+            (json!([["bogus", 3], ["bogus", 5]]), Some(2)),
+            // Blocs imbriqués : la plus profonde
+            (json!([["if", 1, true, [["print", 2, 1], ["while", 3, true, [["print", 4, 1], ["bogus", 5]]]]]]), Some(4)),
+            // Branche else
+            (json!([["if", 1, true, [["print", 2, 1]], [["print", 4]]]]), Some(3)),
+            // Rejetée sans enfant rejeté : sa propre ligne
+            (json!([["while", 2, true, "not a block"]]), Some(1)),
+            // Une expression ["+", 1, 2] n'est pas prise pour une instruction de la ligne 1
+            (json!([["print", 7, ["+", 1, 2]], ["bogus", 8]]), Some(7)),
+        ];
+        for (ast, expected) in cases {
+            assert_eq!(rejected_line(&ast, 0), expected, "{ast}");
+        }
+    }
+
+    #[test]
+    fn loader_error_prefers_the_reported_line() {
+        let ast = json!([["print", 1, "a"], ["bogus", 2]]);
+
+        let located = loader_error(&ast, "Invalid class name (Line 7)");
+        assert_eq!((located.line, located.unlocated), (6, false));
+
+        let fallback = loader_error(&ast, "Instruction inconnue: bogus");
+        assert_eq!((fallback.line, fallback.unlocated), (1, false));
+        assert_eq!(fallback.code, Some(DiagnosticCode::Loader));
+
+        let unlocated = loader_error(&json!([["print", 1, "a"]]), "Block must be a JSON array");
+        assert_eq!((unlocated.line, unlocated.unlocated), (0, true));
+    }
+
+    fn diagnostic(line: u32, code: &str, message: &str, severity: DiagnosticSeverity) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 5)),
+            severity: Some(severity),
+            code: Some(NumberOrString::String(code.to_string())),
+            message: message.to_string(),
+            ..Diagnostic::default()
+        }
+    }
+
+    #[test]
+    fn dedupe_collapses_same_range_and_code() {
+        let deduped = dedupe(vec![
+            diagnostic(1, "loader", "Invalid class name (Line 2)", DiagnosticSeverity::WARNING),
+            diagnostic(1, "loader", "invalid  class name.", DiagnosticSeverity::ERROR),
+        ]);
+        assert_eq!(deduped.len(), 1);
+        assert_eq!(deduped[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(deduped[0].message, "invalid  class name.");
+    }
+
+    #[test]
+    fn dedupe_keeps_different_codes_in_order() {
+        let deduped = dedupe(vec![
+            diagnostic(3, "syntax", "Unexpected token", DiagnosticSeverity::ERROR),
+            diagnostic(1, "unused-variable", "x is never read", DiagnosticSeverity::WARNING),
+            diagnostic(1, "shadowing", "x is never read", DiagnosticSeverity::WARNING),
+            diagnostic(2, "loader", "Unexpected token", DiagnosticSeverity::ERROR),
+        ]);
+        let summary: Vec<_> = deduped.iter().map(|d| (d.range.start.line, d.message.as_str())).collect();
+        assert_eq!(summary, [(3, "Unexpected token"), (1, "x is never read (reported 2 times)"), (2, "Unexpected token")]);
+        assert_eq!(deduped[1].code, Some(NumberOrString::String("unused-variable".to_string())));
+    }
+
+    #[test]
+    fn compiled_program_is_accepted() {
+        let ast = compiler::compile("var x = 1\nif (x > 0) {\n    print x\n}\n").unwrap();
+        assert_eq!(rejected_line(&ast, 0), None);
+    }
+}
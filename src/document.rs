@@ -0,0 +1,412 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+
+use ropey::{Rope, RopeSlice};
+use serde_json::Value;
+use tower_lsp::lsp_types::{Position, PositionEncodingKind, Range, TextDocumentContentChangeEvent, Url};
+
+use crate::line_index::LineIndex;
+
+/// Unité des colonnes LSP, négociée avec le client à l'initialisation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+}
+
+impl PositionEncoding {
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            PositionEncoding::Utf8 => PositionEncodingKind::UTF8,
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+        }
+    }
+
+    pub fn char_len(self, c: char) -> u32 {
+        match self {
+            PositionEncoding::Utf8 => c.len_utf8() as u32,
+            PositionEncoding::Utf16 => c.len_utf16() as u32,
+        }
+    }
+
+    pub fn str_len(self, s: &str) -> u32 {
+        match self {
+            PositionEncoding::Utf8 => s.len() as u32,
+            PositionEncoding::Utf16 => s.encode_utf16().count() as u32,
+        }
+    }
+
+    /// Colonne correspondant à l'offset d'octet `byte` dans `line`.
+    pub fn column(self, line: &str, byte: usize) -> u32 {
+        self.str_len(&line[..byte])
+    }
+}
+
+/// État d'un document ouvert dans l'éditeur.
+#[derive(Debug, Default)]
+pub struct Document {
+    pub rope: Rope,
+    // Version transmise par le client (didOpen / didChange)
+    pub version: i32,
+    // Dernier AST compilé avec succès
+    pub ast: Option<Value>,
+    // Version du texte dont l'AST est issu (None : inconnue)
+    pub ast_version: Option<i32>,
+    // Index des lignes de la version courante, construit à la première demande
+    pub line_index: Option<Arc<LineIndex>>,
+}
+
+/// Buffers des documents ouverts, indexés par Url.
+/// Aucune hypothèse n'est faite sur le schéma : les documents `untitled:` sont gérés
+/// comme les fichiers, seules les fonctionnalités liées au disque les ignorent.
+#[derive(Debug, Default)]
+pub struct DocumentStore {
+    documents: RwLock<HashMap<Url, Document>>,
+    encoding: RwLock<PositionEncoding>,
+    // Documents dont l'AST est en cache, du moins au plus récemment compilé
+    ast_cache: RwLock<VecDeque<Url>>,
+    // Nombre maximal d'AST gardés (None : pas de limite)
+    max_cached_asts: RwLock<Option<usize>>,
+}
+
+impl DocumentStore {
+    pub fn set_encoding(&self, encoding: PositionEncoding) {
+        if let Ok(mut write_guard) = self.encoding.write() {
+            *write_guard = encoding;
+        }
+    }
+
+    pub fn encoding(&self) -> PositionEncoding {
+        self.encoding.read().map(|e| *e).unwrap_or_default()
+    }
+
+    pub fn open(&self, uri: Url, text: String, version: i32) {
+        if let Ok(mut docs) = self.documents.write() {
+            docs.insert(uri, Document { rope: Rope::from_str(&text), version, ast: None, ast_version: None, line_index: None });
+        }
+    }
+
+    /// Applique les changements reçus, dans l'ordre, au buffer du document.
+    /// Un changement sans `range` remplace tout le buffer : lorsqu'une notification
+    /// contient plusieurs textes complets, c'est donc le dernier qui fait foi.
+    /// Les changements d'un document non ouvert sont ignorés (false) : appliqués à un
+    /// buffer vide, ils produiraient un texte sans rapport avec celui de l'éditeur.
+    pub fn change(&self, uri: &Url, changes: &[TextDocumentContentChangeEvent], version: i32) -> bool {
+        let encoding = self.encoding();
+        let Ok(mut docs) = self.documents.write() else {
+            return false;
+        };
+        let Some(doc) = docs.get_mut(uri) else {
+            return false;
+        };
+        for change in changes {
+            apply_change(&mut doc.rope, change, encoding);
+        }
+        doc.version = version;
+        doc.line_index = None;
+        true
+    }
+
+    /// Index des lignes du document, reconstruit paresseusement après chaque modification.
+    pub fn line_index(&self, uri: &Url) -> Option<Arc<LineIndex>> {
+        if let Some(index) = self.documents.read().ok()?.get(uri)?.line_index.clone() {
+            return Some(index);
+        }
+
+        let encoding = self.encoding();
+        let (rope, version) = {
+            let docs = self.documents.read().ok()?;
+            let doc = docs.get(uri)?;
+            (doc.rope.clone(), doc.version)
+        };
+        let index = Arc::new(LineIndex::new(&rope.to_string(), encoding));
+
+        // Le document a pu changer pendant la construction : on ne met en cache que l'index à jour
+        if let Ok(mut docs) = self.documents.write()
+            && let Some(doc) = docs.get_mut(uri)
+            && doc.version == version {
+            doc.line_index = Some(Arc::clone(&index));
+        }
+        Some(index)
+    }
+
+    /// Oublie le document : texte, AST et index des lignes sont libérés.
+    pub fn close(&self, uri: &Url) {
+        if let Ok(mut docs) = self.documents.write() {
+            docs.remove(uri);
+        }
+        if let Ok(mut cache) = self.ast_cache.write() {
+            cache.retain(|u| u != uri);
+        }
+    }
+
+    /// Met en cache l'AST du document, compilé depuis sa version `version`. Au-delà de la
+    /// limite, les AST des documents compilés le moins récemment sont libérés (ils seront
+    /// recompilés au besoin).
+    pub fn set_ast(&self, uri: &Url, ast: Option<Value>, version: Option<i32>) {
+        let Ok(mut docs) = self.documents.write() else {
+            return;
+        };
+        let Some(doc) = docs.get_mut(uri) else {
+            return;
+        };
+        let cached = ast.is_some();
+        doc.ast = ast;
+        doc.ast_version = version.filter(|_| cached);
+
+        if let Ok(mut cache) = self.ast_cache.write() {
+            cache.retain(|u| u != uri);
+            if cached {
+                cache.push_back(uri.clone());
+            }
+            self.evict_asts(&mut docs, &mut cache);
+        }
+    }
+
+    pub fn set_max_cached_asts(&self, max: usize) {
+        if let Ok(mut write_guard) = self.max_cached_asts.write() {
+            *write_guard = Some(max);
+        }
+        if let Ok(mut docs) = self.documents.write()
+            && let Ok(mut cache) = self.ast_cache.write() {
+            self.evict_asts(&mut docs, &mut cache);
+        }
+    }
+
+    fn evict_asts(&self, docs: &mut HashMap<Url, Document>, cache: &mut VecDeque<Url>) {
+        let Some(max) = self.max_cached_asts.read().ok().and_then(|m| *m) else {
+            return;
+        };
+        while cache.len() > max {
+            if let Some(oldest) = cache.pop_front()
+                && let Some(doc) = docs.get_mut(&oldest) {
+                doc.ast = None;
+                doc.ast_version = None;
+            }
+        }
+    }
+
+    /// Nombre de documents ouverts et d'AST en cache.
+    pub fn counts(&self) -> (usize, usize) {
+        let open = self.documents.read().map(|docs| docs.len()).unwrap_or_default();
+        let cached = self.ast_cache.read().map(|cache| cache.len()).unwrap_or_default();
+        (open, cached)
+    }
+
+    /// Documents actuellement ouverts.
+    pub fn uris(&self) -> Vec<Url> {
+        self.documents.read().map(|docs| docs.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Taille du document en octets.
+    pub fn len_bytes(&self, uri: &Url) -> Option<usize> {
+        self.documents.read().ok()?.get(uri).map(|doc| doc.rope.len_bytes())
+    }
+
+    pub fn is_open(&self, uri: &Url) -> bool {
+        self.documents.read().is_ok_and(|docs| docs.contains_key(uri))
+    }
+
+    /// Texte et version courants, lus sous le même verrou.
+    /// La rope est clonée (opération en O(1)) et le texte matérialisé hors du verrou.
+    pub fn snapshot(&self, uri: &Url) -> Option<(String, i32)> {
+        let (rope, version) = {
+            let docs = self.documents.read().ok()?;
+            let doc = docs.get(uri)?;
+            (doc.rope.clone(), doc.version)
+        };
+        Some((rope.to_string(), version))
+    }
+
+    pub fn version(&self, uri: &Url) -> Option<i32> {
+        self.documents.read().ok()?.get(uri).map(|doc| doc.version)
+    }
+
+    /// Exécute `f` sur le document `uri` sous le verrou de lecture.
+    pub fn with_document<R>(&self, uri: &Url, f: impl FnOnce(&Document) -> R) -> Option<R> {
+        self.documents.read().ok()?.get(uri).map(f)
+    }
+
+    pub fn get_text(&self, uri: &Url) -> Option<String> {
+        let rope = self.documents.read().ok()?.get(uri)?.rope.clone();
+        Some(rope.to_string())
+    }
+
+    /// Ligne `line` (base 0) du document, sans le saut de ligne final.
+    pub fn get_line(&self, uri: &Url, line: u32) -> Option<String> {
+        let docs = self.documents.read().ok()?;
+        let rope = &docs.get(uri)?.rope;
+        if line as usize >= rope.len_lines() {
+            return None;
+        }
+        let slice = rope.line(line as usize);
+        Some(slice.slice(..line_content_len(slice)).to_string())
+    }
+
+    /// Texte du document depuis le début jusqu'à `position`.
+    pub fn text_before(&self, uri: &Url, position: Position) -> Option<String> {
+        let docs = self.documents.read().ok()?;
+        let rope = &docs.get(uri)?.rope;
+        Some(rope.slice(..position_to_char(rope, position, self.encoding())).to_string())
+    }
+
+    /// Identifiant situé sous `position`, avec sa plage dans le document.
+    pub fn word_at(&self, uri: &Url, position: Position) -> Option<(String, Range)> {
+        let line = self.get_line(uri, position.line)?;
+        let (word, start, end) = word_at(&line, position.character, self.encoding())?;
+        let range = Range {
+            start: Position { line: position.line, character: start },
+            end: Position { line: position.line, character: end },
+        };
+        Some((word, range))
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Identifiant de `line` qui contient (ou touche) la colonne `character`.
+/// Retourne le mot et ses colonnes de début et de fin.
+pub fn word_at(line: &str, character: u32, encoding: PositionEncoding) -> Option<(String, u32, u32)> {
+    // Colonne de chaque caractère de la ligne
+    let mut chars = Vec::new();
+    let mut col = 0;
+    for c in line.chars() {
+        chars.push((c, col));
+        col += encoding.char_len(c);
+    }
+
+    // Index du caractère sous le curseur ; sinon celui juste avant (curseur en fin de mot)
+    let mut idx = chars.iter().position(|&(_, start)| start >= character).unwrap_or(chars.len());
+    if idx == chars.len() || chars[idx].1 > character || !is_ident_char(chars[idx].0) {
+        if idx == 0 || !is_ident_char(chars[idx - 1].0) {
+            return None;
+        }
+        idx -= 1;
+    }
+
+    let mut first = idx;
+    while first > 0 && is_ident_char(chars[first - 1].0) {
+        first -= 1;
+    }
+    let mut last = idx;
+    while last + 1 < chars.len() && is_ident_char(chars[last + 1].0) {
+        last += 1;
+    }
+
+    let word: String = chars[first..=last].iter().map(|&(c, _)| c).collect();
+    let end = chars[last].1 + encoding.char_len(chars[last].0);
+    Some((word, chars[first].1, end))
+}
+
+/// Nombre de caractères de la ligne, saut de ligne (`\n` ou `\r\n`) exclu.
+fn line_content_len(line: RopeSlice) -> usize {
+    let mut len = line.len_chars();
+    if len > 0 && line.char(len - 1) == '\n' {
+        len -= 1;
+        if len > 0 && line.char(len - 1) == '\r' {
+            len -= 1;
+        }
+    }
+    len
+}
+
+/// Convertit une position LSP en index de caractère dans la rope.
+/// Les positions hors limites sont ramenées à la fin de la ligne ou du document.
+fn position_to_char(rope: &Rope, position: Position, encoding: PositionEncoding) -> usize {
+    let line = position.line as usize;
+    if line >= rope.len_lines() {
+        return rope.len_chars();
+    }
+
+    let line_start = rope.line_to_char(line);
+    let line_end = line_start + line_content_len(rope.line(line));
+    let column = position.character as usize;
+    match encoding {
+        PositionEncoding::Utf8 => {
+            let target = rope.char_to_byte(line_start) + column;
+            rope.byte_to_char(target.min(rope.char_to_byte(line_end)))
+        },
+        PositionEncoding::Utf16 => {
+            let target = rope.char_to_utf16_cu(line_start) + column;
+            rope.utf16_cu_to_char(target.min(rope.char_to_utf16_cu(line_end)))
+        },
+    }
+}
+
+/// Applique un `TextDocumentContentChangeEvent` au buffer.
+/// Sans `range`, le changement remplace tout le document.
+fn apply_change(rope: &mut Rope, change: &TextDocumentContentChangeEvent, encoding: PositionEncoding) {
+    match change.range {
+        Some(range) => {
+            let start = position_to_char(rope, range.start, encoding);
+            let end = position_to_char(rope, range.end, encoding).max(start);
+            rope.remove(start..end);
+            rope.insert(start, &change.text);
+        },
+        None => *rope = Rope::from_str(&change.text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position { line: start.0, character: start.1 },
+                end: Position { line: end.0, character: end.1 },
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn edited(text: &str, change: TextDocumentContentChangeEvent) -> (String, Arc<LineIndex>) {
+        let store = DocumentStore::default();
+        let uri = Url::parse("file:///crlf.aegis").unwrap();
+        store.open(uri.clone(), text.to_string(), 1);
+        store.change(&uri, &[change], 2);
+        (store.get_text(&uri).unwrap(), store.line_index(&uri).unwrap())
+    }
+
+    #[test]
+    fn edit_ending_inside_crlf_keeps_the_terminator() {
+        // La colonne 3 tombe entre `\r` et `\n` : ramenée à la fin du contenu de la ligne
+        let (text, index) = edited("ab\r\ncd\r\n", edit((0, 1), (0, 3), "X"));
+        assert_eq!(text, "aX\r\ncd\r\n");
+        assert_eq!(index.position(text.find('c').unwrap()), Position { line: 1, character: 0 });
+        assert_eq!(index.offset(Position { line: 0, character: 9 }), 2);
+        assert_eq!(index.position(3), Position { line: 0, character: 2 });
+    }
+
+    #[test]
+    fn edit_spanning_crlf_joins_lines() {
+        let (text, index) = edited("ab\r\ncd\r\nef", edit((0, 1), (1, 1), "X"));
+        assert_eq!(text, "aXd\r\nef");
+        for (offset, position) in [(0, (0, 0)), (3, (0, 3)), (5, (1, 0)), (7, (1, 2))] {
+            let position = Position { line: position.0, character: position.1 };
+            assert_eq!(index.position(offset), position);
+            assert_eq!(index.offset(position), offset);
+        }
+    }
+
+    #[test]
+    fn insertion_of_crlf_splits_lines() {
+        let (text, index) = edited("abcd", edit((0, 2), (0, 2), "\r\n"));
+        assert_eq!(text, "ab\r\ncd");
+        assert_eq!(index.offset(Position { line: 1, character: 1 }), 5);
+        assert_eq!(index.position(5), Position { line: 1, character: 1 });
+    }
+
+    #[test]
+    fn change_of_unknown_document_is_ignored() {
+        let store = DocumentStore::default();
+        let uri = Url::parse("file:///closed.aegis").unwrap();
+        assert!(!store.change(&uri, &[edit((0, 0), (0, 0), "print 1")], 2));
+        assert_eq!(store.get_text(&uri), None);
+    }
+}
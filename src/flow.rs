@@ -0,0 +1,117 @@
+use serde_json::Value;
+
+use crate::document::PositionEncoding;
+use crate::symbols;
+
+/// Instructions après lesquelles la suite du bloc ne s'exécute jamais.
+const TERMINATORS: &[&str] = &["return", "break", "continue", "throw"];
+
+/// Instructions dont le bloc `{ ... }` commence sur leur propre ligne.
+const BLOCK_STATEMENTS: &[&str] = &["if", "while", "foreach", "try", "switch", "function", "class", "namespace"];
+
+/// Code inaccessible : pour chaque bloc où une instruction suit un `return` (ou `break`,
+/// `continue`, `throw`), les lignes (base 0) de la première à la dernière instruction qui
+/// le suivent. Seul le bloc du terminateur compte : un `if` qui ne retourne que dans une
+/// branche ne rend pas la suite inaccessible.
+pub fn unreachable(ast: &Value, text: &str) -> Vec<(u32, u32)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut found = Vec::new();
+    walk(ast, &lines, &mut found);
+    found
+}
+
+fn walk(node: &Value, lines: &[&str], found: &mut Vec<(u32, u32)>) {
+    match node {
+        Value::Array(items) => {
+            if let Some(terminator) = items.iter().position(is_terminator)
+                && let (Some(first), Some(last)) = (items.get(terminator + 1).and_then(line), items.last()) {
+                found.push((first, last_line(last, lines)));
+            }
+            for item in items {
+                walk(item, lines, found);
+            }
+        },
+        // Méthodes de classe : { nom: [params, body, ...] }
+        Value::Object(map) => {
+            for item in map.values() {
+                walk(item, lines, found);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Fonctions dont le type de retour déclaré n'est pas `void` mais dont le corps peut se
+/// terminer sans `return` : ligne (base 0) de la déclaration et nom. Une boucle est
+/// supposée pouvoir ne pas s'exécuter ; chaque fonction imbriquée est vérifiée à part.
+pub fn missing_returns(ast: &Value) -> Vec<(u32, &str)> {
+    let mut found = Vec::new();
+    collect_missing_returns(ast, &mut found);
+    found
+}
+
+fn collect_missing_returns<'a>(node: &'a Value, found: &mut Vec<(u32, &'a str)>) {
+    match node {
+        Value::Array(items) => {
+            // ["function", line, "nom", params, ret, body]
+            if node.get(0).and_then(|v| v.as_str()) == Some("function")
+                && node.get(4).and_then(|v| v.as_str()).is_some_and(|ret| ret != "void")
+                && let (Some(line), Some(name)) = (line(node), node.get(2).and_then(|v| v.as_str()))
+                && !node.get(5).is_some_and(always_returns) {
+                found.push((line, name));
+            }
+            for item in items {
+                collect_missing_returns(item, found);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                collect_missing_returns(item, found);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Vrai si chaque chemin du bloc `[instructions...]` se termine par `return` ou `throw`.
+fn always_returns(block: &Value) -> bool {
+    block.as_array().is_some_and(|statements| statements.iter().any(statement_returns))
+}
+
+fn statement_returns(statement: &Value) -> bool {
+    let branch = |i: usize| statement.get(i).is_some_and(always_returns);
+    match statement.get(0).and_then(|v| v.as_str()) {
+        Some("return" | "throw") => true,
+        // ["if", line, cond, then, else]
+        Some("if") => branch(3) && branch(4),
+        // ["try", line, body, err_var, catch_body]
+        Some("try") => branch(2) && branch(4),
+        // ["switch", line, expr, [[valeur, body], ...], default]
+        Some("switch") => {
+            let cases = statement.get(3).and_then(|v| v.as_array());
+            branch(4) && cases.is_some_and(|cases| cases.iter().all(|case| case.get(1).is_some_and(always_returns)))
+        },
+        _ => false,
+    }
+}
+
+fn is_terminator(node: &Value) -> bool {
+    node.get(0).and_then(|v| v.as_str()).is_some_and(|cmd| TERMINATORS.contains(&cmd)) && line(node).is_some()
+}
+
+/// Ligne (base 0) d'une instruction ["cmd", line, ...].
+fn line(node: &Value) -> Option<u32> {
+    node.get(1).and_then(|v| v.as_u64()).map(|l| l.saturating_sub(1) as u32)
+}
+
+/// Dernière ligne d'une instruction, accolade fermante de son bloc comprise.
+fn last_line(node: &Value, lines: &[&str]) -> u32 {
+    let start = line(node).unwrap_or_default();
+    let has_block = node.get(0).and_then(|v| v.as_str()).is_some_and(|cmd| BLOCK_STATEMENTS.contains(&cmd));
+    if has_block {
+        // Seules les lignes comptent : l'encodage des colonnes est indifférent
+        symbols::block_end(lines, start as usize, PositionEncoding::default()).line
+    } else {
+        start
+    }
+}
@@ -0,0 +1,64 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::document::PositionEncoding;
+use crate::symbols::{self, SymbolInfo};
+
+/// Instructions de contrôle dont le bloc peut être replié : ["if", line, ...], ["while", line, ...]...
+const CONTROL_BLOCKS: &[&str] = &["if", "while", "foreach", "try", "switch"];
+
+/// Zones repliables du document : déclarations de bloc (fonctions, classes, méthodes,
+/// namespaces) d'après l'arbre des symboles, puis blocs de contrôle trouvés dans l'AST.
+pub fn folding_ranges(ast: &Value, text: &str) -> Vec<FoldingRange> {
+    let mut lines = Vec::new();
+    // Seules les lignes comptent : l'encodage des colonnes est indifférent
+    collect_declarations(&symbols::symbol_tree(ast, text, PositionEncoding::default()), &mut lines);
+
+    let source: Vec<&str> = text.lines().collect();
+    collect_control_blocks(ast, &source, &mut lines);
+
+    lines.sort_unstable();
+    lines.dedup();
+    lines
+        .into_iter()
+        .filter(|(start, end)| end > start)
+        .map(|(start_line, end_line)| FoldingRange {
+            start_line,
+            end_line,
+            kind: Some(FoldingRangeKind::Region),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn collect_declarations(symbols: &[SymbolInfo], lines: &mut Vec<(u32, u32)>) {
+    for symbol in symbols {
+        if symbol.full_range != symbol.range {
+            lines.push((symbol.full_range.start.line, symbol.full_range.end.line));
+        }
+        collect_declarations(&symbol.children, lines);
+    }
+}
+
+fn collect_control_blocks(ast: &Value, source: &[&str], lines: &mut Vec<(u32, u32)>) {
+    match ast {
+        Value::Array(arr) => {
+            if let (Some(cmd), Some(line)) = (arr.first().and_then(|v| v.as_str()), arr.get(1).and_then(|v| v.as_u64()))
+                && CONTROL_BLOCKS.contains(&cmd) {
+                let start = line.saturating_sub(1) as usize;
+                let end = symbols::block_end(source, start, PositionEncoding::default());
+                lines.push((start as u32, end.line));
+            }
+            for item in arr {
+                collect_control_blocks(item, source, lines);
+            }
+        },
+        // Méthodes de classe : { nom: [params, body, ...] }
+        Value::Object(map) => {
+            for item in map.values() {
+                collect_control_blocks(item, source, lines);
+            }
+        },
+        _ => {}
+    }
+}
@@ -0,0 +1,925 @@
+use std::collections::VecDeque;
+
+use aegis_core::compiler;
+use serde_json::{Number, Value};
+use tower_lsp::lsp_types::{FormattingOptions, Position, Range, TextEdit};
+
+use crate::completion::KEYWORDS;
+use crate::document::PositionEncoding;
+use crate::line_index::LineIndex;
+use crate::panics;
+use crate::references;
+use crate::symbols;
+
+/// Mots réservés du lexer absents de `KEYWORDS` : une clé de dictionnaire portant l'un
+/// d'eux doit rester entre guillemets.
+const RESERVED: &[&str] = &["print", "input", "switch", "case", "default", "in", "prop"];
+
+/// Nœuds portant leur numéro de ligne en deuxième position, ignoré pour comparer deux AST.
+const LINED: &[&str] = &[
+    "set", "set_attr", "print", "return", "input", "import", "try", "throw", "break", "continue",
+    "switch", "namespace", "const", "foreach", "interface", "enum", "function", "if", "while",
+    "class", "call", "call_method", "super_call", "??", "range",
+];
+
+/// Modificateurs pouvant précéder le nom d'un membre de classe.
+const MODIFIERS: &[&str] = &["public", "private", "protected", "static", "final", "func", "var", "prop"];
+
+// Priorités des opérateurs, de la plus faible à la plus forte (cf. parse_expression)
+const TERNARY: u8 = 0;
+const COALESCE: u8 = 1;
+const UNARY: u8 = 10;
+const PRIMARY: u8 = 11;
+
+fn binary_precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 2,
+        "&&" => 3,
+        "==" | "!=" => 4,
+        "<" | ">" | "<=" | ">=" => 5,
+        "&" | "|" | "^" | "<<" | ">>" => 6,
+        "+" | "-" => 8,
+        "*" | "/" | "%" => 9,
+        _ => return None,
+    })
+}
+
+/// Source canonique du document, régénérée depuis son AST : une instruction par ligne,
+/// indentation selon `options`, espaces autour des opérateurs.
+/// Les commentaires `//` seuls sur leur ligne sont replacés avant l'instruction qui les suit.
+/// Renvoie None si l'AST contient un nœud inconnu, si un commentaire ne peut pas être
+/// replacé, ou si le texte produit ne se recompile pas vers le même AST : mieux vaut ne
+/// rien formater que changer le programme.
+pub fn format_document(ast: &Value, text: &str, options: &FormattingOptions) -> Option<String> {
+    format(ast, text, options).map(|(formatted, _)| formatted)
+}
+
+/// Formatage limité aux instructions entièrement comprises dans les lignes de `range` : le
+/// texte entre deux débuts d'instruction (commentaires, accolades fermantes, `else`...)
+/// est remplacé par son équivalent formaté, le reste du document ne bouge pas. Une
+/// sélection qui ne contient aucune instruction entière ne change rien.
+pub fn format_range(ast: &Value, text: &str, range: Range, options: &FormattingOptions, index: &LineIndex) -> Option<Vec<TextEdit>> {
+    let (formatted, starts) = format(ast, text, options)?;
+    let source: Vec<&str> = text.lines().collect();
+    let out: Vec<&str> = formatted.lines().collect();
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    // Découpage commun aux deux textes : en-tête, puis une tranche par début d'instruction
+    let mut boundaries = Vec::new();
+    if starts.first().is_none_or(|&(line, _)| line > 0) {
+        boundaries.push((0, 0));
+    }
+    boundaries.extend(starts);
+    boundaries.push((source.len(), out.len()));
+
+    // Fin de sélection en colonne 0 : la ligne n'est pas sélectionnée
+    let last_line = if range.end.character == 0 && range.end.line > range.start.line { range.end.line - 1 } else { range.end.line } as usize;
+    let selected = |(start, end): (usize, usize)| {
+        let last = (start..end).rev().find(|&l| !source[l].trim().is_empty()).unwrap_or(start);
+        start >= range.start.line as usize && last <= last_line
+    };
+
+    let mut edits = Vec::new();
+    let mut chunks = boundaries.windows(2).peekable();
+    while let Some(chunk) = chunks.next() {
+        if !selected((chunk[0].0, chunk[1].0)) {
+            continue;
+        }
+        let (first, mut last) = (chunk[0], chunk[1]);
+        while let Some(next) = chunks.next_if(|next| selected((next[0].0, next[1].0))) {
+            last = next[1];
+        }
+        let new_text: String = out[first.1..last.1].iter().map(|line| format!("{}{}", line, newline)).collect();
+        let start = Position { line: first.0 as u32, character: 0 };
+        let end = if last.0 >= source.len() { index.position(text.len()) } else { Position { line: last.0 as u32, character: 0 } };
+        if text[index.offset(start)..index.offset(end)] != new_text {
+            edits.push(TextEdit { range: Range { start, end }, new_text });
+        }
+    }
+    Some(edits)
+}
+
+/// Texte formaté, avec la ligne source et la ligne produite du début de chaque instruction.
+fn format(ast: &Value, text: &str, options: &FormattingOptions) -> Option<(String, Vec<(usize, usize)>)> {
+    let comments = line_comments(text)?;
+    let lines: Vec<&str> = text.lines().collect();
+    let crlf = text.contains("\r\n");
+    let mut printer = Printer {
+        lines: &lines,
+        indent: if options.insert_spaces { " ".repeat(options.tab_size as usize) } else { "\t".to_string() },
+        crlf,
+        depth: 0,
+        current: 0,
+        out: Vec::new(),
+        comments: comments.iter().copied().collect(),
+        starts: Vec::new(),
+    };
+
+    // Le shebang est ignoré par le lexer : on le recopie tel quel
+    if text.starts_with("#!") {
+        printer.out.push(lines[0].to_string());
+    }
+    printer.statements(ast.as_array()?)?;
+    printer.flush_comments(usize::MAX);
+
+    let mut formatted = printer.out.join("\n");
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    if crlf {
+        formatted = formatted.replace('\n', "\r\n");
+    }
+
+    // Vérifications : mêmes commentaires, même programme
+    let kept: Vec<&str> = line_comments(&formatted)?.into_iter().map(|(_, c)| c).collect();
+    if kept != comments.into_iter().map(|(_, c)| c).collect::<Vec<_>>() {
+        return None;
+    }
+    let reparsed = panics::catch(|| compiler::compile(&formatted)).ok()?.ok()?;
+    (strip_lines(&reparsed) == strip_lines(ast)).then_some((formatted, printer.starts))
+}
+
+struct Printer<'a> {
+    lines: &'a [&'a str],
+    indent: String,
+    crlf: bool,
+    depth: usize,
+    // Ligne source (base 0) de l'instruction en cours, pour retrouver le sucre syntaxique
+    current: usize,
+    out: Vec<String>,
+    // Commentaires restant à placer, par ligne croissante
+    comments: VecDeque<(usize, &'a str)>,
+    // Ligne source et indice dans `out` du début de chaque instruction, tous deux croissants
+    starts: Vec<(usize, usize)>,
+}
+
+impl Printer<'_> {
+    fn push(&mut self, line: String) {
+        self.out.push(format!("{}{}", self.indent.repeat(self.depth), line));
+    }
+
+    /// Ligne fermant un bloc (`}`, `} else {`...) : un bloc vide reste sur la ligne d'en-tête.
+    fn close(&mut self, text: &str) {
+        match self.out.last_mut() {
+            Some(last) if last.ends_with('{') => last.push_str(text),
+            _ => self.push(text.to_string()),
+        }
+    }
+
+    /// Conserve une ligne vide avant `line` si la source en avait une, sauf en début de bloc.
+    fn separate(&mut self, line: usize) {
+        let blank = line > 0 && self.lines.get(line - 1).is_some_and(|l| l.trim().is_empty());
+        let opening = self.out.last().is_none_or(|l| l.is_empty() || l.ends_with('{') || l.ends_with(':'));
+        if blank && !opening {
+            self.out.push(String::new());
+        }
+    }
+
+    fn flush_comments(&mut self, before: usize) {
+        while let Some(&(line, comment)) = self.comments.front() {
+            if line >= before {
+                break;
+            }
+            self.comments.pop_front();
+            self.separate(line);
+            self.push(comment.to_string());
+        }
+    }
+
+    fn source_line(&self) -> &str {
+        self.lines.get(self.current).copied().unwrap_or("")
+    }
+
+    fn statements(&mut self, body: &[Value]) -> Option<()> {
+        for statement in body {
+            self.statement(statement)?;
+        }
+        Some(())
+    }
+
+    /// Corps d'un bloc ouvert à la ligne source `open`, indenté d'un niveau.
+    /// Renvoie la ligne de l'accolade fermante, d'où part la recherche du bloc suivant (else, catch).
+    fn body(&mut self, body: &Value, open: usize) -> Option<usize> {
+        let end = symbols::block_end(self.lines, open, PositionEncoding::default()).line as usize;
+        self.depth += 1;
+        self.statements(body.as_array()?)?;
+        self.flush_comments(end);
+        self.depth -= 1;
+        Some(end)
+    }
+
+    fn statement(&mut self, statement: &Value) -> Option<()> {
+        let Some(arr) = statement.as_array() else {
+            let expr = self.expr(statement, TERNARY)?;
+            self.push(expr);
+            return Some(());
+        };
+        let cmd = arr.first()?.as_str()?;
+        if LINED.contains(&cmd) && let Some(line) = arr.get(1).and_then(|v| v.as_u64()) {
+            let line = (line as usize).saturating_sub(1);
+            self.flush_comments(line);
+            self.separate(line);
+            self.current = line;
+            if self.starts.last().is_none_or(|&(source, out)| line > source && self.out.len() > out) {
+                self.starts.push((line, self.out.len()));
+            }
+        }
+        let line = self.current;
+
+        match cmd {
+            "set" => self.set(arr)?,
+            "set_attr" => {
+                let target = format!("{}.{}", self.receiver(arr.get(2)?)?, arr.get(3)?.as_str()?);
+                let node = Value::Array(vec!["get_attr".into(), arr[2].clone(), arr[3].clone()]);
+                let assignment = self.assignment(target, &node, arr.get(4)?)?;
+                self.push(assignment);
+            },
+            "print" | "return" | "throw" => {
+                let expr = self.expr(arr.get(2)?, TERNARY)?;
+                self.push(format!("{cmd} {expr}"));
+            },
+            "input" => {
+                let prompt = self.expr(arr.get(3)?, TERNARY)?;
+                self.push(format!("input {} {prompt}", arr.get(2)?.as_str()?));
+            },
+            "import" => {
+                let path = self.string(arr.get(2)?.as_str()?);
+                self.push(format!("import {path}"));
+            },
+            "break" | "continue" => self.push(cmd.to_string()),
+            "if" => match destructured(arr) {
+                Some((vars, expr)) => {
+                    let expr = self.expr(expr, TERNARY)?;
+                    self.push(format!("var [{}] = {expr}", vars.join(", ")));
+                },
+                None => self.if_chain(arr, "")?,
+            },
+            "while" => {
+                let cond = self.expr(arr.get(2)?, TERNARY)?;
+                self.push(format!("while ({cond}) {{"));
+                self.body(arr.get(3)?, line)?;
+                self.close("}");
+            },
+            "foreach" => {
+                let iterable = self.expr(arr.get(3)?, TERNARY)?;
+                self.push(format!("foreach ({} in {iterable}) {{", arr.get(2)?.as_str()?));
+                self.body(arr.get(4)?, line)?;
+                self.close("}");
+            },
+            "namespace" => {
+                self.push(format!("namespace {} {{", arr.get(2)?.as_str()?));
+                self.body(arr.get(3)?, line)?;
+                self.close("}");
+            },
+            "const" => {
+                let expr = self.expr(arr.get(3)?, TERNARY)?;
+                self.push(format!("const {} = {expr}", arr.get(2)?.as_str()?));
+            },
+            "function" => {
+                let ret = arr.get(4).and_then(|v| v.as_str()).map(|t| format!(" -> {t}")).unwrap_or_default();
+                self.push(format!("func {}({}){ret} {{", arr.get(2)?.as_str()?, params(arr.get(3)?)?));
+                self.body(arr.get(5)?, line)?;
+                self.close("}");
+            },
+            "try" => {
+                self.push("try {".to_string());
+                let end = self.body(arr.get(2)?, line)?;
+                self.close(&format!("}} catch ({}) {{", arr.get(3)?.as_str()?));
+                self.body(arr.get(4)?, end)?;
+                self.close("}");
+            },
+            "switch" => self.switch(arr)?,
+            "interface" => {
+                self.push(format!("interface {} {{", arr.get(2)?.as_str()?));
+                let end = symbols::block_end(self.lines, line, PositionEncoding::default()).line as usize;
+                self.depth += 1;
+                for method in arr.get(3)?.as_array()? {
+                    let [name, method_params] = method.as_array()?.as_slice() else { return None };
+                    self.push(format!("func {}({})", name.as_str()?, params(method_params)?));
+                }
+                self.flush_comments(end);
+                self.depth -= 1;
+                self.close("}");
+            },
+            "enum" => {
+                self.push(format!("enum {} {{", arr.get(2)?.as_str()?));
+                let end = symbols::block_end(self.lines, line, PositionEncoding::default()).line as usize;
+                self.depth += 1;
+                let variants = arr.get(3)?.as_array()?;
+                for (i, variant) in variants.iter().enumerate() {
+                    // Pas de virgule finale : le parseur attendrait une variante de plus
+                    let comma = if i + 1 < variants.len() { "," } else { "" };
+                    self.push(format!("{}{comma}", variant.as_str()?));
+                }
+                self.flush_comments(end);
+                self.depth -= 1;
+                self.close("}");
+            },
+            "class" => self.class(arr)?,
+            // Appel en instruction : ["call", line, callee, args] -> ["call", callee, args]
+            "call" | "call_method" | "super_call" if arr.get(1).is_some_and(Value::is_u64) => {
+                let mut call = arr.clone();
+                call.remove(1);
+                let expr = self.expr(&Value::Array(call), TERNARY)?;
+                self.push(expr);
+            },
+            _ => {
+                let expr = self.expr(statement, TERNARY)?;
+                self.push(expr);
+            },
+        }
+        Some(())
+    }
+
+    /// ["set", line, name, type, expr] : déclaration `var`, affectation, ou fonction décorée.
+    fn set(&mut self, arr: &[Value]) -> Option<()> {
+        let name = arr.get(2)?.as_str()?;
+        let annotation = arr.get(3).and_then(|v| v.as_str());
+        let value = arr.get(4)?;
+
+        // Fonction décorée
+        if self.source_line().trim_start().starts_with('@')
+            && let Some((deco, lambda)) = decorated(value)
+        {
+            let line = self.current;
+            self.push(format!("@{deco}"));
+            self.push(format!("func {name}({}) {{", names(lambda.get(1)?)?));
+            self.body(lambda.get(2)?, line)?;
+            self.close("}");
+            return Some(());
+        }
+
+        // `var` ne laisse pas de trace dans l'AST : on le reprend de la ligne source
+        let declared = annotation.is_some()
+            || references::identifiers(self.source_line()).windows(2).any(|w| w[0].1 == "var" && w[1].1 == name);
+        let line = if declared {
+            let typed = annotation.map(|t| format!("{name}: {t}")).unwrap_or_else(|| name.to_string());
+            match value {
+                Value::Null => format!("var {typed}"),
+                _ => format!("var {typed} = {}", self.expr(value, TERNARY)?),
+            }
+        } else {
+            let node = Value::Array(vec!["get".into(), name.into()]);
+            self.assignment(name.to_string(), &node, value)?
+        };
+        self.push(line);
+        Some(())
+    }
+
+    /// `cible op= valeur` et `cible++` sont du sucre pour `cible = cible op valeur` :
+    /// on les reproduit quand la ligne source les utilisait.
+    fn assignment(&mut self, target: String, node: &Value, value: &Value) -> Option<String> {
+        if let Some([op, lhs, rhs]) = value.as_array().map(Vec::as_slice)
+            && lhs == node
+            && let Some(op @ ("+" | "-" | "*" | "/")) = op.as_str()
+        {
+            let source = self.source_line();
+            if matches!(op, "+" | "-") && rhs.as_i64() == Some(1) && source.contains(&format!("{op}{op}")) {
+                return Some(format!("{target}{op}{op}"));
+            }
+            if source.contains(&format!("{op}=")) {
+                return Some(format!("{target} {op}= {}", self.expr(rhs, TERNARY)?));
+            }
+        }
+        Some(format!("{target} = {}", self.expr(value, TERNARY)?))
+    }
+
+    /// ["if", line, cond, then, else?] ; un else ne contenant qu'un if devient `else if`.
+    fn if_chain(&mut self, arr: &[Value], prefix: &str) -> Option<()> {
+        let line = (arr.get(1)?.as_u64()? as usize).saturating_sub(1);
+        self.current = line;
+        let header = format!("{prefix}if ({}) {{", self.expr(arr.get(2)?, TERNARY)?);
+        if prefix.is_empty() {
+            self.push(header);
+        } else {
+            self.close(&header);
+        }
+        let end = self.body(arr.get(3)?, line)?;
+
+        match arr.get(4).and_then(|v| v.as_array()).map(Vec::as_slice) {
+            Some([node]) if node.get(0).and_then(|v| v.as_str()) == Some("if") => {
+                self.if_chain(node.as_array()?, "} else ")?;
+            },
+            Some(_) => {
+                self.close("} else {");
+                self.body(arr.get(4)?, end)?;
+                self.close("}");
+            },
+            None => self.close("}"),
+        }
+        Some(())
+    }
+
+    /// ["switch", line, val, [[case, body]], default]
+    fn switch(&mut self, arr: &[Value]) -> Option<()> {
+        let line = self.current;
+        let value = self.expr(arr.get(2)?, TERNARY)?;
+        self.push(format!("switch ({value}) {{"));
+        let end = symbols::block_end(self.lines, line, PositionEncoding::default()).line as usize;
+
+        self.depth += 1;
+        for case in arr.get(3)?.as_array()? {
+            let [value, body] = case.as_array()?.as_slice() else { return None };
+            let value = self.expr(value, TERNARY)?;
+            self.push(format!("case {value}:"));
+            self.depth += 1;
+            self.statements(body.as_array()?)?;
+            self.depth -= 1;
+        }
+        let default = arr.get(4)?.as_array()?;
+        if !default.is_empty() {
+            self.push("default:".to_string());
+            self.depth += 1;
+            self.statements(default)?;
+            self.depth -= 1;
+        }
+        self.flush_comments(end);
+        self.depth -= 1;
+        self.close("}");
+        Some(())
+    }
+
+    /// ["class", line, name, {méthode: [params, body, static, final]}, parent, [champs], {visibilités}, final, [interfaces]]
+    fn class(&mut self, arr: &[Value]) -> Option<()> {
+        let line = self.current;
+        let name = arr.get(2)?.as_str()?;
+        let methods = arr.get(3)?.as_object()?;
+        let fields = arr.get(5)?.as_array()?;
+        let visibilities = arr.get(6)?.as_object()?;
+
+        let mut header = String::new();
+        if arr.get(7).and_then(|v| v.as_bool()).unwrap_or(false) {
+            header.push_str("final ");
+        }
+        header.push_str(&format!("class {name}"));
+        if let Some(parent) = arr.get(4).and_then(|v| v.as_str()) {
+            header.push_str(&format!(" extends {parent}"));
+        }
+        let interfaces = arr.get(8).and_then(|v| v.as_array()).map(|i| names(&Value::Array(i.clone()))).unwrap_or(Some(String::new()))?;
+        if !interfaces.is_empty() {
+            header.push_str(&format!(" implements {interfaces}"));
+        }
+        self.push(format!("{header} {{"));
+        let end = symbols::block_end(self.lines, line, PositionEncoding::default()).line as usize;
+
+        // Les méthodes sont rangées par nom dans l'AST : on rétablit l'ordre de la source,
+        // sans changer l'ordre relatif des champs qui, lui, compte
+        let mut members = Vec::new();
+        let mut previous = line;
+        for field in fields {
+            previous = previous.max(self.member_line(field.get(1)?.as_str()?, line, end));
+            members.push((previous, None, field));
+        }
+        for (method, def) in methods {
+            members.push((self.member_line(method, line, end), Some(method.as_str()), def));
+        }
+        members.sort_by_key(|&(line, ..)| line);
+
+        self.depth += 1;
+        for (member_line, method, def) in members {
+            self.flush_comments(member_line);
+            self.separate(member_line);
+            self.current = member_line;
+            match method {
+                Some(method) => {
+                    let vis = visibilities.get(method).and_then(|v| v.as_str()).unwrap_or("public");
+                    self.method(method, vis, def.as_array()?)?;
+                },
+                None => self.field(def.as_array()?)?,
+            }
+        }
+        self.flush_comments(end);
+        self.depth -= 1;
+        self.close("}");
+        Some(())
+    }
+
+    /// Ligne de déclaration d'un membre : la première où il suit ses éventuels modificateurs.
+    fn member_line(&self, name: &str, from: usize, to: usize) -> usize {
+        (from + 1..to)
+            .find(|&l| {
+                let idents = references::identifiers(self.lines[l]);
+                idents.iter().find(|(_, i)| !MODIFIERS.contains(i)).is_some_and(|&(_, i)| i == name)
+            })
+            .unwrap_or(from)
+    }
+
+    /// [params, body, static, final] ; les méthodes déclarées sans `func` n'ont pas le booléen `final`.
+    fn method(&mut self, name: &str, vis: &str, def: &[Value]) -> Option<()> {
+        let mut header = modifiers(vis, def.get(2).and_then(|v| v.as_bool()).unwrap_or(false));
+        match def.get(3).map(|v| v.as_bool()) {
+            Some(Some(true)) => header.push_str("final func "),
+            Some(_) => header.push_str("func "),
+            None => {},
+        }
+        self.push(format!("{header}{name}({}) {{", params(def.first()?)?));
+        self.body(def.get(1)?, self.current)?;
+        self.close("}");
+        Some(())
+    }
+
+    /// ["field", name, vis, default, static, type] ou ["prop", name, vis, static, getter, setter]
+    fn field(&mut self, field: &[Value]) -> Option<()> {
+        let name = field.get(1)?.as_str()?;
+        let vis = field.get(2)?.as_str()?;
+        let line = self.current;
+
+        if field.first()?.as_str()? == "prop" {
+            let is_static = field.get(3)?.as_bool()?;
+            self.push(format!("{}prop {name} {{", modifiers(vis, is_static)));
+            self.depth += 1;
+            if let Some([_, body]) = field.get(4)?.as_array().map(Vec::as_slice) {
+                self.push("get {".to_string());
+                self.body(body, line)?;
+                self.close("}");
+            }
+            if let Some([setter_params, body]) = field.get(5)?.as_array().map(Vec::as_slice) {
+                self.push(format!("set({}) {{", params(setter_params)?));
+                self.body(body, line)?;
+                self.close("}");
+            }
+            self.depth -= 1;
+            self.close("}");
+            return Some(());
+        }
+
+        let mut declaration = format!("{}var {name}", modifiers(vis, field.get(4)?.as_bool()?));
+        if let Some(annotation) = field.get(5).and_then(|v| v.as_str()) {
+            declaration.push_str(&format!(": {annotation}"));
+        }
+        match field.get(3)? {
+            Value::Null => {},
+            default => declaration.push_str(&format!(" = {}", self.expr(default, TERNARY)?)),
+        }
+        self.push(declaration);
+        Some(())
+    }
+
+    fn expr(&mut self, value: &Value, min: u8) -> Option<String> {
+        let (text, precedence) = self.expr_precedence(value)?;
+        Some(if precedence < min { format!("({text})") } else { text })
+    }
+
+    /// Opérande placé devant `.` ou `(` : un nombre y serait lu comme un flottant (`1.x`).
+    fn receiver(&mut self, value: &Value) -> Option<String> {
+        match value {
+            Value::Number(n) => Some(format!("({})", number(n))),
+            _ => self.expr(value, PRIMARY),
+        }
+    }
+
+    fn args(&mut self, args: &Value) -> Option<String> {
+        let args = args.as_array()?.iter().map(|a| self.expr(a, TERNARY)).collect::<Option<Vec<_>>>()?;
+        Some(args.join(", "))
+    }
+
+    fn expr_precedence(&mut self, value: &Value) -> Option<(String, u8)> {
+        let arr = match value {
+            Value::Null => return Some(("null".to_string(), PRIMARY)),
+            Value::Bool(b) => return Some((b.to_string(), PRIMARY)),
+            Value::Number(n) => return Some((number(n), PRIMARY)),
+            Value::String(s) => return Some((self.string(s), PRIMARY)),
+            Value::Array(arr) => arr.as_slice(),
+            Value::Object(_) => return None,
+        };
+
+        let text = match (arr.first()?.as_str()?, &arr[1..]) {
+            ("get", [name]) => name.as_str()?.to_string(),
+            ("get_attr", [obj, member]) => format!("{}.{}", self.receiver(obj)?, member.as_str()?),
+            ("call", [callee, args]) => format!("{}({})", self.receiver(callee)?, self.args(args)?),
+            ("call_method", [obj, member, args]) => {
+                format!("{}.{}({})", self.receiver(obj)?, member.as_str()?, self.args(args)?)
+            },
+            ("super_call", [method, args]) => format!("super.{}({})", method.as_str()?, self.args(args)?),
+            ("new", [callee, args @ ..]) => {
+                let args = self.args(&Value::Array(args.to_vec()))?;
+                format!("new {}({args})", dotted(callee)?)
+            },
+            ("lambda", [lambda_params, body]) => {
+                let header = format!("func({})", names(lambda_params)?);
+                let saved = std::mem::take(&mut self.out);
+                self.body(body, self.current)?;
+                let lines = std::mem::replace(&mut self.out, saved);
+                if lines.is_empty() {
+                    format!("{header} {{}}")
+                } else {
+                    format!("{header} {{\n{}\n{}}}", lines.join("\n"), self.indent.repeat(self.depth))
+                }
+            },
+            ("make_list", items) => format!("[{}]", self.args(&Value::Array(items.to_vec()))?),
+            ("make_dict", entries) => {
+                let mut pairs = Vec::new();
+                for entry in entries {
+                    let [key, value] = entry.as_array()?.as_slice() else { return None };
+                    let key = key.as_str()?;
+                    let key = if is_plain_key(key) { key.to_string() } else { self.string(key) };
+                    pairs.push(format!("{key}: {}", self.expr(value, TERNARY)?));
+                }
+                format!("{{{}}}", pairs.join(", "))
+            },
+            ("?", [cond, then, otherwise]) => {
+                let text = format!(
+                    "{} ? {} : {}",
+                    self.expr(cond, COALESCE)?,
+                    self.expr(then, TERNARY)?,
+                    self.expr(otherwise, TERNARY)?,
+                );
+                return Some((text, TERNARY));
+            },
+            ("??", [_, left, right]) => {
+                let text = format!("{} ?? {}", self.expr(left, COALESCE)?, self.expr(right, COALESCE + 1)?);
+                return Some((text, COALESCE));
+            },
+            // Les bornes d'un intervalle sont des expressions additives
+            ("range", [_, start, end]) => {
+                let text = format!("{}..{}", self.expr(start, 8)?, self.expr(end, 8)?);
+                return Some((text, 7));
+            },
+            ("!", [operand]) => return Some((format!("!{}", self.expr(operand, UNARY)?), UNARY)),
+            // Moins unaire : ["-", 0, x]
+            ("-", [zero, operand]) if zero.as_i64() == Some(0) => {
+                let operand = self.expr(operand, UNARY)?;
+                // `--x` serait lu comme l'opérateur de décrément
+                let text = if operand.starts_with('-') { format!("-({operand})") } else { format!("-{operand}") };
+                return Some((text, UNARY));
+            },
+            (op, [left, right]) => {
+                let precedence = binary_precedence(op)?;
+                if op == "+" && let Some(text) = self.interpolation(value) {
+                    return Some((text, UNARY));
+                }
+                let text = format!("{} {op} {}", self.expr(left, precedence)?, self.expr(right, precedence + 1)?);
+                return Some((text, precedence));
+            },
+            _ => return None,
+        };
+        Some((text, PRIMARY))
+    }
+
+    /// Chaîne `"...${expr}..."` rétablie depuis la concaténation produite par le parseur,
+    /// quand la ligne source contenait une interpolation. Le littéral obtenu ne peut pas
+    /// être suivi de `.` ou `(` : il a la priorité d'une expression unaire.
+    fn interpolation(&mut self, value: &Value) -> Option<String> {
+        if !self.source_line().contains("${") {
+            return None;
+        }
+
+        let mut parts = Vec::new();
+        let mut node = value;
+        while let Some([op, left, right]) = node.as_array().map(Vec::as_slice)
+            && op.as_str() == Some("+")
+        {
+            parts.push(right);
+            node = left;
+        }
+        parts.push(node);
+        parts.reverse();
+        if !parts.iter().any(|p| p.is_string()) || parts.windows(2).any(|w| w[0].is_string() && w[1].is_string()) {
+            return None;
+        }
+
+        let mut text = String::from('"');
+        for part in parts {
+            if let Value::String(s) = part {
+                if s.contains("${") {
+                    return None;
+                }
+                text.push_str(&escape(s));
+                continue;
+            }
+
+            // ["call", ["get", "fmt"], [expr, "spec"]] : `${expr:spec}`
+            let format = part
+                .as_array()
+                .filter(|a| a.first().and_then(|v| v.as_str()) == Some("call"))
+                .filter(|a| a.get(1).and_then(|c| c.get(1)).and_then(|v| v.as_str()) == Some("fmt"))
+                .and_then(|a| a.get(2)?.as_array())
+                .and_then(|args| match args.as_slice() {
+                    [expr, Value::String(spec)] => Some((expr, spec.as_str())),
+                    _ => None,
+                });
+            let (code, spec) = match format {
+                Some((expr, spec)) => (self.expr(expr, TERNARY)?, Some(spec)),
+                None => (self.expr(part, TERNARY)?, None),
+            };
+            // Le code interpolé est délimité par les accolades et ne peut pas contenir de guillemet
+            if code.contains(['"', '`', '\\', ':', '{', '}', '\n']) || spec.is_some_and(|s| s.contains(['"', '\\', '}', '$'])) {
+                return None;
+            }
+            text.push_str("${");
+            text.push_str(&code);
+            if let Some(spec) = spec {
+                text.push(':');
+                text.push_str(spec);
+            }
+            text.push('}');
+        }
+        text.push('"');
+        Some(text)
+    }
+
+    /// Littéral de chaîne : entre guillemets, ou entre backticks s'il s'étend sur plusieurs
+    /// lignes ou contient `${` qui serait lu comme une interpolation.
+    fn string(&self, s: &str) -> String {
+        if !s.contains('\n') && !s.contains("${") {
+            return format!("\"{}\"", escape(s));
+        }
+
+        let mut text = String::from('`');
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '`' => text.push_str("\\`"),
+                '\\' => text.push_str("\\\\"),
+                '$' if chars.peek() == Some(&'{') => text.push_str("\\$"),
+                // Les sauts de ligne du texte produit deviennent `\r\n` dans un fichier CRLF
+                '\r' if self.crlf && chars.peek() == Some(&'\n') => {
+                    chars.next();
+                    text.push('\n');
+                },
+                '\r' => text.push_str("\\r"),
+                '\n' if self.crlf => text.push_str("\\n"),
+                c => text.push(c),
+            }
+        }
+        text.push('`');
+        text
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Un flottant garde sa partie décimale pour rester un flottant une fois relu.
+fn number(n: &Number) -> String {
+    match n.as_f64() {
+        Some(f) if n.is_f64() => {
+            let text = f.to_string();
+            if text.contains('.') { text } else { format!("{text}.0") }
+        },
+        _ => n.to_string(),
+    }
+}
+
+fn modifiers(vis: &str, is_static: bool) -> String {
+    let mut text = String::new();
+    if vis != "public" {
+        text.push_str(vis);
+        text.push(' ');
+    }
+    if is_static {
+        text.push_str("static ");
+    }
+    text
+}
+
+/// Paramètres typés : [[nom, type|null]] -> "a, b: int"
+fn params(params: &Value) -> Option<String> {
+    let labels = params
+        .as_array()?
+        .iter()
+        .map(|p| {
+            let name = p.get(0)?.as_str()?;
+            Some(match p.get(1).and_then(|t| t.as_str()) {
+                Some(t) => format!("{name}: {t}"),
+                None => name.to_string(),
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(labels.join(", "))
+}
+
+/// Liste de noms : ["a", "b"] -> "a, b"
+fn names(names: &Value) -> Option<String> {
+    let names = names.as_array()?.iter().map(|n| n.as_str()).collect::<Option<Vec<_>>>()?;
+    Some(names.join(", "))
+}
+
+/// Classe instanciée par `new` : ["get", n] ou ["get_attr", ..., m]
+fn dotted(callee: &Value) -> Option<String> {
+    match callee.as_array()?.as_slice() {
+        [cmd, name] if cmd.as_str() == Some("get") => Some(name.as_str()?.to_string()),
+        [cmd, obj, member] if cmd.as_str() == Some("get_attr") => Some(format!("{}.{}", dotted(obj)?, member.as_str()?)),
+        _ => None,
+    }
+}
+
+fn is_plain_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&key)
+        && !RESERVED.contains(&key)
+}
+
+/// `@deco func name(...) { }` : ["call", ["get", deco], [["lambda", params, body]]]
+fn decorated(value: &Value) -> Option<(&str, &[Value])> {
+    let [cmd, callee, args] = value.as_array()?.as_slice() else { return None };
+    let [get, deco] = callee.as_array()?.as_slice() else { return None };
+    let [lambda] = args.as_array()?.as_slice() else { return None };
+    let lambda = lambda.as_array()?;
+    let matches = cmd.as_str() == Some("call") && get.as_str() == Some("get") && lambda.first()?.as_str() == Some("lambda");
+    (matches && lambda.len() == 3).then_some((deco.as_str()?, lambda.as_slice()))
+}
+
+/// `var [a, b] = e` : ["if", line, true, [set __destruct_temp_N = e, set a = temp.at(0), ...]]
+fn destructured(arr: &[Value]) -> Option<(Vec<&str>, &Value)> {
+    if arr.get(2)? != &Value::Bool(true) || arr.len() != 4 {
+        return None;
+    }
+    let (first, rest) = arr[3].as_array()?.split_first()?;
+    let temp = first.get(2)?.as_str()?;
+    if first.get(0)?.as_str()? != "set" || temp != format!("__destruct_temp_{}", rest.len()) {
+        return None;
+    }
+
+    let mut vars = Vec::new();
+    for (i, set) in rest.iter().enumerate() {
+        let expected = serde_json::json!(["call_method", ["get", temp], "at", [i]]);
+        if set.get(0)?.as_str()? != "set" || set.get(4)? != &expected {
+            return None;
+        }
+        vars.push(set.get(2)?.as_str()?);
+    }
+    Some((vars, first.get(4)?))
+}
+
+/// Copie de l'AST sans numéros de ligne, qui changent forcément au formatage.
+fn strip_lines(value: &Value) -> Value {
+    match value {
+        Value::Array(arr) => {
+            let mut arr: Vec<Value> = arr.iter().map(strip_lines).collect();
+            if arr.first().and_then(|v| v.as_str()).is_some_and(|cmd| LINED.contains(&cmd))
+                && arr.get(1).is_some_and(Value::is_u64)
+            {
+                arr[1] = Value::Null;
+            }
+            Value::Array(arr)
+        },
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), strip_lines(v))).collect()),
+        _ => value.clone(),
+    }
+}
+
+/// Commentaires `//` seuls sur leur ligne, avec leur ligne (base 0).
+/// None si un commentaire suit du code ou s'il s'agit d'un bloc `/* */` : l'AST ne garde
+/// pas les commentaires et le formateur ne saurait pas où les remettre.
+fn line_comments(text: &str) -> Option<Vec<(usize, &str)>> {
+    let mut comments = Vec::new();
+    let mut line = 0;
+    let mut line_start = 0;
+    let mut quote = None;
+    let mut chars = text.char_indices().peekable();
+
+    // Shebang ignoré, comme par le lexer
+    if text.starts_with("#!") {
+        while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+    }
+
+    while let Some((i, c)) = chars.next() {
+        if c == '\n' {
+            line += 1;
+            line_start = i + 1;
+        }
+        if let Some(q) = quote {
+            match c {
+                '\\' => {
+                    if let Some((j, '\n')) = chars.next() {
+                        line += 1;
+                        line_start = j + 1;
+                    }
+                },
+                c if c == q => quote = None,
+                _ => {},
+            }
+            continue;
+        }
+
+        match c {
+            '"' | '`' => quote = Some(c),
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '/') => {
+                if !text[line_start..i].trim().is_empty() {
+                    return None;
+                }
+                let end = text[i..].find('\n').map_or(text.len(), |n| i + n);
+                comments.push((line, text[i..end].trim_end()));
+                while chars.next_if(|&(j, _)| j < end).is_some() {}
+            },
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '*') => return None,
+            _ => {},
+        }
+    }
+    Some(comments)
+}
@@ -0,0 +1,140 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+/// Langue des diagnostics transmis au client (`aegis.locale`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+/// Messages produits par le serveur : modèle anglais et traduction française. Chaque `{}`
+/// marque une partie variable, reprise telle quelle (et traduite à son tour) dans le même
+/// ordre ; le premier modèle reconnu l'emporte.
+const MESSAGES: &[(&str, &str)] = &[
+    // Suffixes ajoutés à un autre message : essayés d'abord
+    ("{} (reported {} times)", "{} (signalé {} fois)"),
+    ("{} (position not reported by the {})", "{} (position non indiquée par : {})"),
+    ("{}; did you mean {}?", "{} ; vouliez-vous dire {} ?"),
+    // Messages complets
+    ("'{}' is already defined", "'{}' est déjà défini"),
+    ("first defined here", "première définition ici"),
+    ("Variable '{}' is never read", "La variable '{}' n'est jamais lue"),
+    ("Unreachable code", "Code inaccessible"),
+    ("'{}' does not return a value on every path", "'{}' ne renvoie pas de valeur dans tous les cas"),
+    ("'{}' is deprecated", "'{}' est obsolète"),
+    ("'{}' constructor expects {} argument, found {}", "le constructeur de '{}' attend {} argument, {} fourni(s)"),
+    ("'{}' constructor expects {} arguments, found {}", "le constructeur de '{}' attend {} arguments, {} fourni(s)"),
+    ("'{}' expects {} argument, found {}", "'{}' attend {} argument, {} fourni(s)"),
+    ("'{}' expects {} arguments, found {}", "'{}' attend {} arguments, {} fourni(s)"),
+    ("'{}' is not a member of class '{}'", "'{}' n'est pas un membre de la classe '{}'"),
+    ("'{}' shadows an outer declaration", "'{}' masque une déclaration englobante"),
+    ("shadowed declaration", "déclaration masquée"),
+    ("'{}' is not defined", "'{}' n'est pas défini"),
+    ("Cannot find imported file '{}'", "Fichier importé '{}' introuvable"),
+    ("Imported file '{}' has errors: {}", "Le fichier importé '{}' contient des erreurs : {}"),
+    ("{} additional problems not shown", "{} autres problèmes non affichés"),
+    ("internal compiler error: {}", "erreur interne du compilateur : {}"),
+    (
+        "Analysis disabled for large files ({} KB, limit aegis.maxFileSizeKb = {} KB)",
+        "Analyse désactivée pour les fichiers volumineux ({} Ko, limite aegis.maxFileSizeKb = {} Ko)",
+    ),
+];
+
+/// Modèles anglais de `MESSAGES` sous forme d'expressions régulières.
+static PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    MESSAGES
+        .iter()
+        .map(|(english, _)| {
+            let pattern = english.split("{}").map(regex::escape).collect::<Vec<_>>().join("(.*)");
+            Regex::new(&format!("^{}$", pattern)).unwrap()
+        })
+        .collect()
+});
+
+/// Positions des messages du compilateur : "(Line X)", "[Ligne X, Col Y]"...
+static LINE_PREFIXES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[(\[](?:Line|Ligne) (?P<rest>\d+[^()\[\]]*)[)\]]").unwrap());
+
+/// Colonnes de ces positions : ", Col Y", ", Colonne Y"...
+static COLUMNS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r",\s*[[:alpha:]]+\s*(?P<number>\d+)").unwrap());
+
+/// `message` dans la langue `locale` : traduction des messages du serveur, et positions du
+/// compilateur ramenées au format "(Line X, Column Y)" / "(Ligne X, Colonne Y)", quelle
+/// que soit leur forme d'origine.
+pub fn localize(message: &str, locale: Locale) -> String {
+    let (line, column) = match locale {
+        Locale::En => ("Line", "Column"),
+        Locale::Fr => ("Ligne", "Colonne"),
+    };
+    let message = LINE_PREFIXES.replace_all(message, |captures: &regex::Captures| {
+        let rest = COLUMNS.replace_all(&captures["rest"], format!(", {} ${{number}}", column).as_str());
+        format!("({} {})", line, rest)
+    });
+    translate(&message, locale)
+}
+
+fn translate(message: &str, locale: Locale) -> String {
+    if locale == Locale::En {
+        return message.to_string();
+    }
+    let Some((i, captures)) = PATTERNS.iter().enumerate().find_map(|(i, pattern)| Some((i, pattern.captures(message)?))) else {
+        return message.to_string();
+    };
+    let mut parts = captures.iter().skip(1).flatten().map(|part| translate(part.as_str(), locale));
+    MESSAGES[i]
+        .1
+        .split("{}")
+        .enumerate()
+        .map(|(n, piece)| if n == 0 { piece.to_string() } else { format!("{}{}", parts.next().unwrap_or_default(), piece) })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `template` dont le n-ième `{}` devient "v<n>"
+    fn fill(template: &str) -> String {
+        template
+            .split("{}")
+            .enumerate()
+            .map(|(n, piece)| if n == 0 { piece.to_string() } else { format!("v{}{}", n - 1, piece) })
+            .collect()
+    }
+
+    #[test]
+    fn every_message_is_translated() {
+        for (english, french) in MESSAGES {
+            let message = fill(english);
+            assert_eq!(localize(&message, Locale::En), message);
+            assert_eq!(localize(&message, Locale::Fr), fill(french), "{english}");
+        }
+    }
+
+    #[test]
+    fn nested_messages_are_translated() {
+        let message = "'x' is not defined (reported 2 times)";
+        assert_eq!(localize(message, Locale::Fr), "'x' n'est pas défini (signalé 2 fois)");
+    }
+
+    #[test]
+    fn positions_use_the_locale_wording() {
+        for message in ["Unexpected token [Ligne 5, Colonne 3]", "Unexpected token (Line 5, Col 3)"] {
+            assert_eq!(localize(message, Locale::En), "Unexpected token (Line 5, Column 3)");
+            assert_eq!(localize(message, Locale::Fr), "Unexpected token (Ligne 5, Colonne 3)");
+        }
+        assert_eq!(localize("Missing '}' [Line 4]", Locale::Fr), "Missing '}' (Ligne 4)");
+    }
+
+    #[test]
+    fn unknown_messages_pass_through() {
+        let message = "Instruction inconnue: bogus";
+        assert_eq!(localize(message, Locale::En), message);
+        assert_eq!(localize(message, Locale::Fr), message);
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use aegis_core::compiler;
+use serde_json::Value;
+
+use crate::diagnostics::{self, CompileError};
+use crate::panics;
+
+/// État d'un fichier importé, d'après son contenu sur disque.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportStatus {
+    // Aucun fichier à ce chemin
+    Missing,
+    // Le fichier ne compile pas : sa première erreur
+    Broken(CompileError),
+    Valid,
+}
+
+/// Résolution des `import` : chemins essayés dans l'ordre, et état de chaque fichier
+/// candidat, gardé en cache jusqu'à ce que l'observateur de fichiers le signale modifié.
+#[derive(Debug, Default)]
+pub struct ImportResolver {
+    files: RwLock<HashMap<PathBuf, ImportStatus>>,
+}
+
+impl ImportResolver {
+    /// État du fichier que désigne `import` : le premier candidat qui existe parmi le
+    /// dossier du document, puis chaque dossier de `search_paths` (le dossier courant du
+    /// runtime est en général la racine du workspace).
+    pub fn resolve(&self, import: &str, document_dir: &Path, search_paths: &[PathBuf]) -> ImportStatus {
+        self.find(import, document_dir, search_paths).map_or(ImportStatus::Missing, |(_, status)| status)
+    }
+
+    /// Chemin du fichier que désigne `import`, dans le même ordre que `resolve`.
+    pub fn resolve_path(&self, import: &str, document_dir: &Path, search_paths: &[PathBuf]) -> Option<PathBuf> {
+        self.find(import, document_dir, search_paths).map(|(path, _)| path)
+    }
+
+    fn find(&self, import: &str, document_dir: &Path, search_paths: &[PathBuf]) -> Option<(PathBuf, ImportStatus)> {
+        let candidates: Vec<PathBuf> = if Path::new(import).is_absolute() {
+            vec![PathBuf::from(import)]
+        } else {
+            std::iter::once(document_dir)
+                .chain(search_paths.iter().map(PathBuf::as_path))
+                .map(|dir| dir.join(import))
+                .collect()
+        };
+        candidates
+            .into_iter()
+            .map(|path| {
+                let status = self.status(&path);
+                (path, status)
+            })
+            .find(|(_, status)| *status != ImportStatus::Missing)
+    }
+
+    /// Oublie l'état de `path` (fichier créé, modifié ou supprimé). Vrai s'il était en cache.
+    pub fn invalidate(&self, path: &Path) -> bool {
+        self.files.write().is_ok_and(|mut files| files.remove(path).is_some())
+    }
+
+    fn status(&self, path: &Path) -> ImportStatus {
+        if let Some(status) = self.files.read().ok().and_then(|files| files.get(path).cloned()) {
+            return status;
+        }
+
+        let status = match fs::read_to_string(path) {
+            Err(_) => ImportStatus::Missing,
+            // Le lexer peut paniquer sur une entrée invalide
+            Ok(text) => match panics::catch(|| compiler::compile(&text)) {
+                Ok(Ok(_)) => ImportStatus::Valid,
+                Ok(Err(e)) => {
+                    let first = diagnostics::split_errors(&e).into_iter().next().unwrap_or(e);
+                    ImportStatus::Broken(CompileError::from(first.as_str()))
+                },
+                Err(_) => ImportStatus::Broken(CompileError::from("Lexer error")),
+            },
+        };
+        if let Ok(mut files) = self.files.write() {
+            files.insert(path.to_path_buf(), status.clone());
+        }
+        status
+    }
+}
+
+/// `import` du document : ligne (base 0) de l'instruction et chemin importé.
+pub fn imports(ast: &Value) -> Vec<(u32, &str)> {
+    let mut found = Vec::new();
+    collect(ast, &mut found);
+    found
+}
+
+fn collect<'a>(node: &'a Value, found: &mut Vec<(u32, &'a str)>) {
+    match node {
+        Value::Array(arr) => {
+            // ["import", line, "chemin"]
+            if arr.first().and_then(|v| v.as_str()) == Some("import")
+                && let (Some(line), Some(path)) = (arr.get(1).and_then(|v| v.as_u64()), arr.get(2).and_then(|v| v.as_str())) {
+                found.push((line.saturating_sub(1) as u32, path));
+            }
+            for item in arr {
+                collect(item, found);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                collect(item, found);
+            }
+        },
+        _ => {}
+    }
+}
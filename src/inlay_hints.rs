@@ -0,0 +1,216 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::{CompletionItemKind, InlayHint, InlayHintKind, InlayHintLabel, Position, Range};
+
+use crate::document::PositionEncoding;
+use crate::line_index::LineIndex;
+use crate::references;
+use crate::symbols::{self, SymbolInfo};
+
+/// Opérateurs arithmétiques : entier si les deux opérandes le sont, sinon flottant.
+const ARITHMETIC: &[&str] = &["+", "-", "*", "/", "%"];
+
+/// Opérateurs qui produisent toujours un booléen.
+const BOOLEAN: &[&str] = &["<", "<=", ">", ">=", "==", "!=", "&&", "||", "!"];
+
+/// Types des variables déclarées par `var` sans annotation, affichés après leur nom
+/// (`var x = 1` devient `var x: int = 1`), pour les seules lignes de `range`. Le type est
+/// déduit de la valeur initiale lorsqu'elle est littérale ou construite par `new`.
+pub fn type_hints(ast: &Value, text: &str, range: Range, encoding: PositionEncoding) -> Vec<InlayHint> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut hints = Vec::new();
+    walk(ast, &lines, range, encoding, &mut hints);
+    hints
+}
+
+fn walk(node: &Value, lines: &[&str], range: Range, encoding: PositionEncoding, hints: &mut Vec<InlayHint>) {
+    match node {
+        Value::Array(arr) => {
+            // ["set", line, "nom", type, expr] : l'annotation explicite est déjà visible
+            if arr.first().and_then(|v| v.as_str()) == Some("set")
+                && arr.get(3).is_some_and(Value::is_null)
+                && let (Some(line), Some(name), Some(type_name)) = (
+                    arr.get(1).and_then(|v| v.as_u64()).map(|l| l.saturating_sub(1) as u32),
+                    arr.get(2).and_then(|v| v.as_str()),
+                    arr.get(4).and_then(inferred_type),
+                )
+                && (range.start.line..=range.end.line).contains(&line)
+                && let Some(character) = lines.get(line as usize).and_then(|text| declared_name_end(text, name, encoding)) {
+                hints.push(hint(Position { line, character }, format!(": {}", type_name), InlayHintKind::TYPE));
+            }
+            for item in arr {
+                walk(item, lines, range, encoding, hints);
+            }
+        },
+        // Méthodes de classe : { nom: [params, body, ...] }
+        Value::Object(map) => {
+            for item in map.values() {
+                walk(item, lines, range, encoding, hints);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Noms des paramètres devant les arguments des appels de `functions` (fonctions et
+/// méthodes connues) : `add(1, 2)` devient `add(x: 1, y: 2)`, pour les seules positions de
+/// `range`. Les arguments en trop sont ignorés, de même qu'un argument qui porte déjà le
+/// nom de son paramètre.
+pub fn parameter_hints(text: &str, index: &LineIndex, range: Range, functions: &[SymbolInfo]) -> Vec<InlayHint> {
+    let identifiers = references::identifiers(text);
+    let mut hints = Vec::new();
+    for (i, &(offset, name)) in identifiers.iter().enumerate() {
+        let rest = &text[offset + name.len()..];
+        let Some(open) = rest.trim_start().strip_prefix('(') else {
+            continue;
+        };
+        // Déclaration `func nom(a, b)` plutôt qu'appel
+        if i > 0 && identifiers[i - 1].1 == "func" {
+            continue;
+        }
+        let Some(function) = functions.iter().find(|s| {
+            s.name == name && matches!(s.kind, CompletionItemKind::FUNCTION | CompletionItemKind::METHOD)
+        }) else {
+            continue;
+        };
+
+        let Some((arguments, close)) = arguments(open) else {
+            continue;
+        };
+        // Méthode déclarée dans une classe : `nom(a, b) { ... }`
+        if open[close + 1..].trim_start().starts_with('{') {
+            continue;
+        }
+
+        let base = text.len() - open.len();
+        for (start, param) in arguments.into_iter().zip(&function.params) {
+            let param = param.split(':').next().unwrap_or(param).trim();
+            let argument = &open[start..];
+            if argument.strip_prefix(param).is_some_and(|after| !after.starts_with(|c: char| c.is_alphanumeric() || c == '_')) {
+                continue;
+            }
+            let position = index.position(base + start);
+            if contains(range, position) {
+                hints.push(InlayHint { padding_right: Some(true), ..hint(position, format!("{}:", param), InlayHintKind::PARAMETER) });
+            }
+        }
+    }
+    hints
+}
+
+/// Début (offset d'octet dans `args`) de chaque argument d'une liste qui suit sa parenthèse
+/// ouvrante, et offset de la parenthèse fermante. None si la liste n'est pas refermée.
+fn arguments(args: &str) -> Option<(Vec<usize>, usize)> {
+    let mut starts = Vec::new();
+    let mut expecting = true;
+    let mut depth = 0;
+    let mut chars = args.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if expecting && !matches!(c, ')' | ',') {
+            starts.push(i);
+            expecting = false;
+        }
+        match c {
+            '"' | '`' => {
+                while let Some((_, n)) = chars.next() {
+                    match n {
+                        '\\' => { chars.next(); },
+                        n if n == c => break,
+                        _ => {}
+                    }
+                }
+            },
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '/') => {
+                for (_, n) in chars.by_ref() {
+                    if n == '\n' {
+                        break;
+                    }
+                }
+            },
+            '(' | '[' | '{' => depth += 1,
+            ')' if depth == 0 => return Some((starts, i)),
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => expecting = true,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn hint(position: Position, label: String, kind: InlayHintKind) -> InlayHint {
+    InlayHint {
+        position,
+        label: InlayHintLabel::String(label),
+        kind: Some(kind),
+        text_edits: None,
+        tooltip: None,
+        padding_left: None,
+        padding_right: None,
+        data: None,
+    }
+}
+
+fn contains(range: Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+/// Colonne qui suit `name` dans `var name` sur la ligne `text`. None pour une simple
+/// réaffectation (`x = 2`) ou une déstructuration, qui n'ont pas de `var` devant le nom.
+fn declared_name_end(text: &str, name: &str, encoding: PositionEncoding) -> Option<u32> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices("var").find_map(|(i, _)| {
+        let before = text[..i].chars().next_back().is_none_or(|c| !is_ident(c));
+        let rest = &text[i + "var".len()..];
+        let name_start = rest.len() - rest.trim_start().len();
+        if !before || name_start == 0 {
+            return None;
+        }
+        let after = rest[name_start..].strip_prefix(name)?;
+        if after.starts_with(is_ident) {
+            return None;
+        }
+        Some(encoding.column(text, text.len() - after.len()))
+    })
+}
+
+/// Type Aegis (celui que donne `typeof`) d'une expression, quand il se déduit sans exécution.
+fn inferred_type(expr: &Value) -> Option<String> {
+    let name = match expr {
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "int",
+        Value::String(_) => "string",
+        Value::Bool(_) => "bool",
+        Value::Array(arr) => {
+            let op = arr.first()?.as_str()?;
+            match op {
+                "make_list" => "list",
+                "make_dict" => "dict",
+                "lambda" => "function",
+                "range" => "range",
+                "new" => return symbols::value_type(None, Some(expr)),
+                // Le `+` concatène dès qu'un opérande est une chaîne
+                "+" if arr[1..].iter().any(|v| inferred_type(v).as_deref() == Some("string")) => "string",
+                _ if ARITHMETIC.contains(&op) => {
+                    let left = inferred_type(arr.get(1)?)?;
+                    let right = inferred_type(arr.get(2)?)?;
+                    match (left.as_str(), right.as_str()) {
+                        ("int", "int") => "int",
+                        ("int" | "float", "int" | "float") => "float",
+                        _ => return None,
+                    }
+                },
+                _ if BOOLEAN.contains(&op) => "bool",
+                // ["?", condition, alors, sinon] : typé si les deux branches s'accordent
+                "?" => {
+                    let then = inferred_type(arr.get(2)?)?;
+                    return (inferred_type(arr.get(3)?)? == then).then_some(then);
+                },
+                _ => return None,
+            }
+        },
+        _ => return None,
+    };
+    Some(name.to_string())
+}
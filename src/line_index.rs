@@ -0,0 +1,154 @@
+use tower_lsp::lsp_types::Position;
+
+use crate::document::PositionEncoding;
+
+/// Table des débuts de ligne d'un texte, pour convertir offsets d'octets et positions LSP
+/// sans recompter les lignes à chaque requête.
+/// Comme dans la rope du document, `\r\n` est un seul terminateur et le `\r` ne fait pas
+/// partie de la ligne : les colonnes d'un fichier CRLF sont celles de sa version LF.
+#[derive(Debug)]
+pub struct LineIndex {
+    encoding: PositionEncoding,
+    // Offset du début de chaque ligne
+    starts: Vec<usize>,
+    // Offset de fin du contenu de chaque ligne, hors `\n` / `\r\n`
+    ends: Vec<usize>,
+    // Caractères multi-octets de chaque ligne, avec leur offset relatif au début de ligne
+    wide: Vec<Vec<(usize, char)>>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str, encoding: PositionEncoding) -> Self {
+        let mut starts = vec![0];
+        let mut ends = Vec::new();
+        let mut wide = Vec::new();
+        let mut current = Vec::new();
+
+        for (i, c) in text.char_indices() {
+            if c == '\n' {
+                let end = if text[..i].ends_with('\r') { i - 1 } else { i };
+                ends.push(end);
+                wide.push(std::mem::take(&mut current));
+                starts.push(i + 1);
+            } else if c.len_utf8() > 1 {
+                current.push((i - starts[starts.len() - 1], c));
+            }
+        }
+        // Dernière ligne, éventuellement sans saut de ligne final
+        ends.push(text.len());
+        wide.push(current);
+
+        LineIndex { encoding, starts, ends, wide }
+    }
+
+    /// Offset d'octet correspondant à `position`.
+    /// Une colonne au-delà de la fin de ligne est ramenée à la fin de la ligne,
+    /// une ligne au-delà de la fin du texte à la fin du texte.
+    pub fn offset(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.starts.len() {
+            return self.ends[self.ends.len() - 1];
+        }
+
+        let start = self.starts[line];
+        let target = position.character as usize;
+        let mut units = 0;
+        let mut prev = 0;
+        for &(at, c) in &self.wide[line] {
+            // Entre deux caractères multi-octets, une unité vaut un octet
+            if target <= units + at - prev {
+                return start + prev + target - units;
+            }
+            units += at - prev;
+
+            // Colonne au milieu d'un caractère : on se place au début de celui-ci
+            let width = self.encoding.char_len(c) as usize;
+            if target < units + width {
+                return start + at;
+            }
+            units += width;
+            prev = at + c.len_utf8();
+        }
+        (start + prev + target - units).min(self.ends[line])
+    }
+
+    /// Position LSP correspondant à l'offset d'octet `offset`.
+    /// Un offset dans un saut de ligne est ramené à la fin du contenu de la ligne,
+    /// un offset au-delà de la fin du texte à la fin du texte.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.ends[self.ends.len() - 1]);
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let column = offset.min(self.ends[line]) - self.starts[line];
+
+        let mut units = 0;
+        let mut prev = 0;
+        for &(at, c) in &self.wide[line] {
+            if at >= column {
+                break;
+            }
+            units += at - prev;
+            // Offset au milieu d'un caractère : on se place au début de celui-ci
+            if at + c.len_utf8() > column {
+                return Position { line: line as u32, character: units as u32 };
+            }
+            units += self.encoding.char_len(c) as usize;
+            prev = at + c.len_utf8();
+        }
+        Position { line: line as u32, character: (units + column - prev) as u32 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn surrogate_pairs_count_as_two_utf16_units() {
+        // "😀" : 4 octets, 2 unités UTF-16
+        let index = LineIndex::new("a😀b\nxyz", PositionEncoding::Utf16);
+        assert_eq!(index.offset(pos(0, 1)), 1);
+        assert_eq!(index.offset(pos(0, 3)), 5);
+        assert_eq!(index.position(5), pos(0, 3));
+        // Colonne ou offset au milieu de la paire : début du caractère
+        assert_eq!(index.offset(pos(0, 2)), 1);
+        assert_eq!(index.position(3), pos(0, 1));
+
+        let utf8 = LineIndex::new("a😀b", PositionEncoding::Utf8);
+        assert_eq!(utf8.offset(pos(0, 5)), 5);
+        assert_eq!(utf8.position(5), pos(0, 5));
+    }
+
+    #[test]
+    fn clamps_past_end_of_line_and_file() {
+        let index = LineIndex::new("a😀b\nxyz", PositionEncoding::Utf16);
+        assert_eq!(index.offset(pos(0, 99)), 6);
+        assert_eq!(index.position(6), pos(0, 4));
+        assert_eq!(index.offset(pos(7, 0)), 10);
+        assert_eq!(index.position(100), pos(1, 3));
+    }
+
+    #[test]
+    fn last_line_without_trailing_newline() {
+        let index = LineIndex::new("ab\ncd", PositionEncoding::Utf16);
+        assert_eq!(index.offset(pos(1, 2)), 5);
+        assert_eq!(index.offset(pos(1, 9)), 5);
+        assert_eq!(index.position(5), pos(1, 2));
+
+        let index = LineIndex::new("ab\n", PositionEncoding::Utf16);
+        assert_eq!(index.offset(pos(1, 0)), 3);
+        assert_eq!(index.position(3), pos(1, 0));
+    }
+
+    #[test]
+    fn round_trips_every_offset() {
+        let text = "é😀\nx\r\n\ty€z";
+        let index = LineIndex::new(text, PositionEncoding::Utf16);
+        for (offset, _) in text.char_indices().filter(|&(i, _)| !text[..i].ends_with('\r')) {
+            assert_eq!(index.offset(index.position(offset)), offset, "offset {}", offset);
+        }
+    }
+}
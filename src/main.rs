@@ -1,70 +1,501 @@
-use std::sync::RwLock;
+mod completion;
+mod diagnostics;
+mod arity;
+mod builtins;
+mod call_hierarchy;
+mod code_actions;
+mod deprecated;
+mod document;
+mod flow;
+mod folding;
+mod formatting;
+mod i18n;
+mod imports;
+mod inlay_hints;
+mod line_index;
+mod members;
+mod on_type;
+mod panics;
+mod references;
+mod selection;
+mod semantic_tokens;
+mod settings;
+mod shadowing;
+mod symbols;
+mod undefined;
+mod workspace;
 
-use tower_lsp::jsonrpc::Result;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 use aegis_core::{compiler, loader};
 use serde_json::Value;
+use diagnostics::{DiagnosticCode, Report};
+use document::{DocumentStore, PositionEncoding};
+use i18n::Locale;
+use imports::{ImportResolver, ImportStatus};
+use line_index::LineIndex;
+use settings::{Settings, ValidateOn};
+use symbols::SymbolInfo;
+use workspace::WorkspaceIndex;
 
-#[derive(Debug)]
+/// Durée d'échecs de compilation au-delà de laquelle les symboles gardés sont signalés
+/// comme anciens dans la complétion.
+const STALE_SYMBOLS: Duration = Duration::from_secs(5 * 60);
+
+/// Commande qui renvoie l'AST compilé d'un document, pour le débogage.
+const DUMP_AST_COMMAND: &str = "aegis.dumpAst";
+
+/// Commande qui compile un document à la demande et affiche le résultat.
+const COMPILE_COMMAND: &str = "aegis.compile";
+
+/// Commande qui affiche les versions du serveur et du compilateur.
+const VERSION_COMMAND: &str = "aegis.version";
+
+const COMMANDS: &[&str] = &[DUMP_AST_COMMAND, COMPILE_COMMAND, VERSION_COMMAND];
+
+/// Version du compilateur `aegis-lang` utilisé, relevée dans Cargo.lock par build.rs.
+const COMPILER_VERSION: &str = env!("AEGIS_COMPILER_VERSION");
+
+// L'état est partagé via des `Arc` : le clone est bon marché et peut être
+// déplacé dans les tâches de fond (indexation, validation différée).
+#[derive(Debug, Clone)]
 struct Backend {
     client: Client,
-    symbols: RwLock<Vec<CompletionItem>>
+    symbols: Arc<RwLock<HashMap<Url, Vec<SymbolInfo>>>>,
+    documents: Arc<DocumentStore>,
+    settings: Arc<RwLock<Settings>>,
+    workspace: Arc<WorkspaceIndex>,
+    // État des fichiers importés par les documents ouverts
+    imports: Arc<ImportResolver>,
+    client_capabilities: Arc<RwLock<ClientCapabilities>>,
+    // File de validation de chaque document ouvert (consommée par une tâche dédiée)
+    validations: Arc<RwLock<HashMap<Url, mpsc::UnboundedSender<Validation>>>>,
+    // Jeton de la version courante de chaque document, annulé à chaque modification
+    cancellations: Arc<RwLock<HashMap<Url, CancellationToken>>>,
+    // Derniers diagnostics de chaque document ouvert, servis par `textDocument/diagnostic`
+    reports: Arc<RwLock<HashMap<Url, Report>>>,
+    // Début de la série d'échecs de compilation de chaque document ouvert qui ne compile plus
+    failing_since: Arc<RwLock<HashMap<Url, Instant>>>,
+    // Empreinte des derniers diagnostics publiés (mode push) de chaque document
+    published: Arc<RwLock<HashMap<Url, u64>>>,
+}
+
+/// Réponse de la requête `aegis/memory`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MemoryStats {
+    open_documents: usize,
+    cached_asts: usize,
+    max_cached_documents: usize,
+    symbol_lists: usize,
+    workspace_files: usize,
+}
+
+/// Résultat de `run_analysis`.
+struct Analysis {
+    diagnostics: Vec<Diagnostic>,
+    // Messages d'erreur arrivés sans position, à signaler dans le log
+    unlocated: Vec<String>,
+}
+
+/// Notification `$/progress` portant une partie des résultats de `workspace/diagnostic`.
+enum WorkspaceDiagnosticProgress {}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkspaceDiagnosticProgressParams {
+    token: ProgressToken,
+    value: WorkspaceDiagnosticReportPartialResult,
+}
+
+impl notification::Notification for WorkspaceDiagnosticProgress {
+    type Params = WorkspaceDiagnosticProgressParams;
+    const METHOD: &'static str = "$/progress";
+}
+
+/// Nombre de fichiers par envoi partiel de `workspace/diagnostic`.
+const WORKSPACE_REPORT_BATCH: usize = 20;
+
+/// Demande de validation d'un document.
+#[derive(Debug)]
+struct Validation {
+    text: String,
+    version: Option<i32>,
+    token: CancellationToken,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(options) = &params.initialization_options {
+            let parsed = self.read_settings(options).await;
+            if let Ok(mut settings) = self.settings.write() {
+                *settings = parsed;
+            }
+        }
+        self.documents.set_max_cached_asts(self.max_cached_documents());
+
+        if let Ok(mut write_guard) = self.client_capabilities.write() {
+            *write_guard = params.capabilities.clone();
+        }
+
+        // UTF-8 si le client le propose, sinon UTF-16 (valeur par défaut du protocole)
+        let offers_utf8 = params.capabilities.general
+            .as_ref()
+            .and_then(|g| g.position_encodings.as_ref())
+            .is_some_and(|encodings| encodings.contains(&PositionEncodingKind::UTF8));
+        let encoding = if offers_utf8 { PositionEncoding::Utf8 } else { PositionEncoding::Utf16 };
+        self.documents.set_encoding(encoding);
+
+        // Dossiers du workspace (ou à défaut root_uri), indexés dans `initialized`
+        let roots: Vec<PathBuf> = match &params.workspace_folders {
+            Some(folders) => folders.iter().filter_map(|f| f.uri.to_file_path().ok()).collect(),
+            None => params.root_uri.iter().filter_map(|uri| uri.to_file_path().ok()).collect(),
+        };
+        for root in roots {
+            self.workspace.add_folder(root);
+        }
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                position_encoding: Some(encoding.kind()),
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                            include_text: Some(true),
+                        })),
+                        ..Default::default()
+                    },
                 )),
                 completion_provider: Some(CompletionOptions {
                     resolve_provider: Some(false),
                     trigger_characters: Some(vec![".".to_string(), ":".to_string()]),
                     ..Default::default()
                 }),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(true) }),
+                code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                    code_action_kinds: Some(vec![CodeActionKind::QUICKFIX, CodeActionKind::SOURCE_ORGANIZE_IMPORTS]),
+                    ..Default::default()
+                })),
+                semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+                    SemanticTokensOptions {
+                        legend: semantic_tokens::legend(),
+                        full: Some(SemanticTokensFullOptions::Bool(true)),
+                        ..Default::default()
+                    },
+                )),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec!["(".to_string()]),
+                    retrigger_characters: Some(vec![",".to_string()]),
+                    work_done_progress_options: Default::default(),
+                }),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: on_type::TRIGGERS[0].to_string(),
+                    more_trigger_character: Some(on_type::TRIGGERS[1..].iter().map(|c| c.to_string()).collect()),
+                }),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: COMMANDS.iter().map(|command| command.to_string()).collect(),
+                    work_done_progress_options: Default::default(),
+                }),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                    identifier: Some("aegis".to_string()),
+                    inter_file_dependencies: false,
+                    workspace_diagnostics: true,
+                    work_done_progress_options: Default::default(),
+                })),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 ..Default::default()
             },
-            ..Default::default()
+            server_info: Some(ServerInfo {
+                name: env!("CARGO_PKG_NAME").to_string(),
+                version: Some(format!("{} (aegis-lang {})", env!("CARGO_PKG_VERSION"), COMPILER_VERSION)),
+            }),
         })
     }
 
     async fn initialized(&self, _: InitializedParams) {
         self.client
-            .log_message(MessageType::INFO, "Aegis LSP (v0.4.1) initialized!")
+            .log_message(
+                MessageType::INFO,
+                format!("Aegis LSP (v{}, aegis-lang {}) initialized!", env!("CARGO_PKG_VERSION"), COMPILER_VERSION),
+            )
             .await;
+
+        self.register_file_watcher().await;
+        if let Some(settings) = self.fetch_settings().await {
+            self.apply_settings(settings).await;
+        }
+
+        self.index_folders(self.workspace.folders());
+    }
+
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        for folder in params.event.removed {
+            let Ok(root) = folder.uri.to_file_path() else { continue };
+            for uri in self.workspace.remove_folder(&root) {
+                if !self.documents.is_open(&uri) {
+                    self.clear_published(uri).await;
+                }
+            }
+        }
+
+        let added: Vec<PathBuf> = params.event.added
+            .iter()
+            .filter_map(|f| f.uri.to_file_path().ok())
+            .collect();
+        for root in &added {
+            self.workspace.add_folder(root.clone());
+        }
+        self.index_folders(added);
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        self.validate_document(params.text_document.uri, params.text_document.text).await;
+        let uri = params.text_document.uri;
+        let text = params.text_document.text;
+        let version = params.text_document.version;
+
+        self.documents.open(uri.clone(), text.clone(), version);
+        let token = self.renew_token(&uri);
+        self.schedule_validation(uri, text, Some(version), token);
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        if let Some(change) = params.content_changes.into_iter().next() {
-            self.validate_document(params.text_document.uri, change.text).await;
-        }
-    }
-
-    async fn completion(&self, _: CompletionParams) -> Result<Option<CompletionResponse>> {
-        // 1. Liste mise à jour avec la nouvelle syntaxe
-        let keywords = vec![
-            // Structures de contrôle
-            "if", "else", "while", "foreach", "return", "break", "continue",
-            "try", "catch", "throw",
-            // Déclarations
-            "var", "const", "func", "class", "interface", "namespace", "enum",
-            // Modificateurs & OOP
-            "new", "extends", "implements", "this", "super",
-            "public", "private", "protected", "static", "final",
-            "import", "from",
-            // Valeurs
-            "true", "false", "null"
-        ];
-
-        let mut items: Vec<CompletionItem> = keywords
-            .into_iter()
+        let uri = params.text_document.uri;
+
+        // On applique les modifications (incrémentales ou complètes) au buffer du document
+        if !self.documents.change(&uri, &params.content_changes, params.text_document.version) {
+            self.client.log_message(MessageType::WARNING, format!("Aegis LSP: change ignored for unopened document {}", uri)).await;
+            return;
+        }
+
+        // La validation en cours porte sur un texte périmé : on l'abandonne
+        let token = self.renew_token(&uri);
+
+        // En mode "save", la frappe ne déclenche pas de validation
+        if self.validate_on() != ValidateOn::Change {
+            return;
+        }
+
+        // Validation différée : seule la dernière modification d'une rafale est compilée
+        let backend = self.clone();
+        let version = params.text_document.version;
+        let delay = self.diagnostics_debounce();
+        tokio::spawn(async move {
+            // Une frappe plus récente ou la fermeture du document annule le jeton : le
+            // minuteur est alors abandonné sans rien publier
+            tokio::select! {
+                _ = token.cancelled() => return,
+                _ = tokio::time::sleep(delay) => {},
+            }
+
+            // Fichier trop volumineux : inutile de matérialiser son texte
+            if backend.is_too_large(&uri) {
+                if backend.documents.version(&uri) == Some(version) {
+                    let size = backend.documents.len_bytes(&uri).unwrap_or_default();
+                    let diagnostics = vec![backend.disable_analysis(&uri, size)];
+                    backend.publish(uri, diagnostics, Some(version)).await;
+                }
+                return;
+            }
+
+            match backend.documents.snapshot(&uri) {
+                Some((text, current)) if current == version => {
+                    backend.schedule_validation(uri, text, Some(version), token);
+                },
+                // Une modification plus récente a programmé sa propre validation
+                _ => {},
+            }
+        });
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let version = self.documents.version(&uri);
+        let text = match params.text {
+            Some(text) => text,
+            None => match self.documents.get_text(&uri) {
+                Some(text) => text,
+                None => return,
+            },
+        };
+
+        let token = self.current_token(&uri);
+        self.schedule_validation(uri, text, version, token);
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Un client qui gère `workspace/configuration` n'envoie en général pas les réglages eux-mêmes
+        let settings = match self.fetch_settings().await {
+            Some(settings) => settings,
+            None => self.read_settings(&params.settings).await,
+        };
+        self.apply_settings(settings).await;
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+
+        self.documents.close(&uri);
+        self.stop_validations(&uri);
+        if let Ok(mut tokens) = self.cancellations.write()
+            && let Some(token) = tokens.remove(&uri) {
+            token.cancel();
+        }
+        if let Ok(mut write_guard) = self.symbols.write() {
+            write_guard.remove(&uri);
+        }
+        if let Ok(mut reports) = self.reports.write() {
+            reports.remove(&uri);
+        }
+        if let Ok(mut failing) = self.failing_since.write() {
+            failing.remove(&uri);
+        }
+        if let Ok(mut published) = self.published.write() {
+            published.remove(&uri);
+        }
+
+        // Le contenu sur disque reprend la main dans l'index du workspace
+        match uri.to_file_path() {
+            Ok(path) if self.workspace.index_file(&path, self.documents.encoding(), self.max_file_size()) => {},
+            _ => self.workspace.remove(&uri),
+        }
+
+        // On efface les diagnostics du document fermé uniquement ; en mode pull, ceux du
+        // contenu sur disque prennent le relais
+        if self.pulls_diagnostics() {
+            self.refresh_diagnostics().await;
+        } else {
+            self.clear_published(uri).await;
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut imports_changed = false;
+        for event in params.changes {
+            let uri = event.uri;
+            if let Ok(path) = uri.to_file_path() {
+                imports_changed |= self.imports.invalidate(&path);
+            }
+            match event.typ {
+                FileChangeType::DELETED => {
+                    self.workspace.remove(&uri);
+                    if !self.documents.is_open(&uri) {
+                        self.clear_published(uri).await;
+                    }
+                },
+                _ => {
+                    // Un document ouvert fait foi sur le contenu du disque
+                    if self.documents.is_open(&uri) {
+                        continue;
+                    }
+                    if let Ok(path) = uri.to_file_path() {
+                        self.workspace.index_file(&path, self.documents.encoding(), self.max_file_size());
+                    }
+                }
+            }
+        }
+
+        // Un fichier importé a changé : les documents ouverts revérifient leurs imports
+        if imports_changed {
+            for uri in self.documents.uris() {
+                if let Some((text, version)) = self.documents.snapshot(&uri) {
+                    let token = self.current_token(&uri);
+                    self.schedule_validation(uri, text, Some(version), token);
+                }
+            }
+        }
+        self.refresh_diagnostics().await;
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        // Fichier trop volumineux pour être analysé : mots-clés uniquement
+        let too_large = self.is_too_large(uri);
+
+        // Pas de suggestions dans une chaîne ou un commentaire
+        let prefix = if too_large { String::new() } else { self.documents.text_before(uri, position).unwrap_or_default() };
+        if completion::in_string_or_comment(&prefix) {
+            return Ok(None);
+        }
+
+        let trigger = params.context.as_ref().and_then(|c| c.trigger_character.as_deref());
+
+        // À la place d'un type : les types natifs des annotations
+        if completion::in_type_annotation(&prefix) {
+            let items = completion::TYPES
+                .iter()
+                .map(|t| CompletionItem {
+                    label: t.to_string(),
+                    kind: Some(CompletionItemKind::KEYWORD),
+                    ..Default::default()
+                })
+                .collect();
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+        // Après `Namespace.` / `Namespace::` : uniquement le contenu du namespace
+        if let Some(path) = completion::namespace_path(&prefix)
+            && let Some(items) = self.namespace_completions(uri, &path) {
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+        // Un `:` de dictionnaire, de ternaire ou de `case` n'appelle pas de suggestions
+        if trigger == Some(":") {
+            return Ok(None);
+        }
+
+        // Après `objet.` : les membres de sa classe, si on sait la déterminer
+        if let Some(receiver) = completion::member_receiver(&prefix)
+            && let Some(items) = self.member_completions(uri, receiver, position) {
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+        // Sinon, un `.` tapé ne doit pas ouvrir la liste générale
+        if trigger == Some(".") {
+            return Ok(None);
+        }
+
+        // Après `new`, `extends`, `implements` : uniquement des classes ou des interfaces
+        let expected = completion::expected_kind(&prefix);
+
+        // 1. Mots-clés du langage
+        let mut items: Vec<CompletionItem> = completion::KEYWORDS
+            .iter()
+            .filter(|_| expected.is_none())
             .map(|k| CompletionItem {
                 label: k.to_string(),
                 kind: Some(CompletionItemKind::KEYWORD),
@@ -72,283 +503,1375 @@ impl LanguageServer for Backend {
             })
             .collect();
 
-        // 2. Ajouter les symboles dynamiques
-        if let Ok(read_guard) = self.symbols.read() {
-            items.extend(read_guard.clone());
+        if too_large {
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
+        // 2. Ajouter les symboles dynamiques accessibles depuis le curseur
+        let mut visible: Vec<SymbolInfo> = match self.symbols.read() {
+            Ok(read_guard) => read_guard
+                .get(uri)
+                .map(|symbols| symbols.iter().filter(|s| s.visible_at(position)).cloned().collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        // Symboles d'une version qui compilait il y a longtemps : signalés comme tels
+        if let Some(minutes) = self.stale_minutes(uri) {
+            for symbol in &mut visible {
+                symbol.detail = format!("{} (from the last version that compiled, {} min ago)", symbol.detail, minutes);
+            }
+        }
+
+        // 3. Puis les déclarations des autres fichiers du workspace (hors namespaces, qui
+        // ne se complètent qu'après leur chemin)
+        visible.extend(self.workspace.exported_symbols(uri).into_iter().filter(|s| s.namespace.is_none()));
+        if let Some(kind) = expected {
+            visible.retain(|s| s.kind == kind);
         }
 
+        let snippets = self.snippet_support();
+        items.extend(symbols::dedup(&visible).into_iter().map(|s| s.completion_item(snippets)));
+
         Ok(Some(CompletionResponse::Array(items)))
     }
 
-    async fn shutdown(&self) -> Result<()> {
-        Ok(())
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some((word, range)) = self.documents.word_at(uri, position) else {
+            return Ok(None);
+        };
+
+        let symbol = match self.symbols.read() {
+            Ok(read_guard) => read_guard
+                .get(uri)
+                .and_then(|symbols| symbols.iter().find(|s| s.name == word).cloned()),
+            Err(_) => None,
+        };
+
+        Ok(symbol.map(|s| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```aegis\n{}\n```\n{}", s.name, s.detail),
+            }),
+            range: Some(range),
+        }))
     }
-}
 
-impl Backend {
-    async fn validate_document(&self, uri: Url, text: String) {
-        let mut diagnostics = Vec::new();
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
 
-        // On utilise le compilateur Aegis
-        match compiler::compile(&text) {
-            Ok(json_ast) => {
-                // Extraction des symboles pour l'autocomplétion
-                let found_symbols = self.extract_symbols(&json_ast);
-                
-                if let Ok(mut write_guard) = self.symbols.write() {
-                    *write_guard = found_symbols;
+        let Some(prefix) = self.documents.text_before(uri, position) else {
+            return Ok(None);
+        };
+        if completion::in_string_or_comment(&prefix) {
+            return Ok(None);
+        }
+        let Some((callee, active)) = completion::call_context(&prefix) else {
+            return Ok(None);
+        };
+
+        // Fonctions et méthodes du document, puis celles des autres fichiers
+        let mut candidates: Vec<SymbolInfo> = match self.symbols.read() {
+            Ok(read_guard) => read_guard.get(uri).cloned().unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        candidates.extend(self.workspace.exported_symbols(uri));
+
+        let Some(function) = candidates.into_iter().find(|s| {
+            s.name == callee && matches!(s.kind, CompletionItemKind::FUNCTION | CompletionItemKind::METHOD)
+        }) else {
+            return Ok(None);
+        };
+
+        let parameters = function
+            .params
+            .iter()
+            .map(|p| ParameterInformation { label: ParameterLabel::Simple(p.clone()), documentation: None })
+            .collect();
+
+        Ok(Some(SignatureHelp {
+            signatures: vec![SignatureInformation {
+                label: function.signature(),
+                documentation: None,
+                parameters: Some(parameters),
+                active_parameter: Some(active),
+            }],
+            active_signature: Some(0),
+            active_parameter: Some(active),
+        }))
+    }
+
+    async fn goto_definition(&self, params: GotoDefinitionParams) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some((word, _)) = self.documents.word_at(uri, position) else {
+            return Ok(None);
+        };
+
+        let mut locations: Vec<Location> = match self.symbols.read() {
+            Ok(read_guard) => read_guard
+                .get(uri)
+                .map(|symbols| {
+                    symbols
+                        .iter()
+                        .filter(|s| s.name == word)
+                        .map(|s| Location { uri: uri.clone(), range: s.range })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        // Pas de déclaration locale : fichiers importés, puis tout le workspace
+        if locations.is_empty() {
+            locations = self.imported_definitions(uri, &word);
+        }
+        if locations.is_empty() {
+            locations = self.workspace.definitions(&word, uri);
+        }
+
+        Ok(match locations.len() {
+            0 => None,
+            1 => locations.pop().map(GotoDefinitionResponse::Scalar),
+            _ => Some(GotoDefinitionResponse::Array(locations)),
+        })
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let Some((word, _)) = self.documents.word_at(uri, position) else {
+            return Ok(None);
+        };
+        // Première version : toutes les occurrences du nom, sans tenir compte du masquage
+        let ranges = if params.context.include_declaration { self.occurrences(uri, &word) } else { self.uses(uri, &word) };
+        let locations = ranges.into_iter().map(|range| Location { uri: uri.clone(), range }).collect();
+
+        Ok(Some(locations))
+    }
+
+    async fn document_highlight(&self, params: DocumentHighlightParams) -> Result<Option<Vec<DocumentHighlight>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some((word, _)) = self.documents.word_at(uri, position) else {
+            return Ok(None);
+        };
+        // Déclarations et affectations du nom : écritures, les autres occurrences sont des lectures
+        let writes: Vec<Range> = match self.symbols.read() {
+            Ok(read_guard) => read_guard
+                .get(uri)
+                .map(|symbols| symbols.iter().filter(|s| s.name == word).map(|s| s.range).collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+
+        let highlights: Vec<DocumentHighlight> = self
+            .occurrences(uri, &word)
+            .into_iter()
+            .map(|range| DocumentHighlight {
+                range,
+                kind: Some(if writes.contains(&range) { DocumentHighlightKind::WRITE } else { DocumentHighlightKind::READ }),
+            })
+            .collect();
+
+        Ok((!highlights.is_empty()).then_some(highlights))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+        let (Some(text), Some(index)) = (self.documents.get_text(uri), self.documents.line_index(uri)) else {
+            return Ok(None);
+        };
+        // Le client peut restreindre les sortes d'actions voulues (`only`)
+        let wanted = |kind: &CodeActionKind| {
+            params.context.only.as_ref().is_none_or(|only| only.iter().any(|o| kind.as_str().starts_with(o.as_str())))
+        };
+
+        let mut actions = Vec::new();
+        if wanted(&CodeActionKind::QUICKFIX) {
+            actions.extend(code_actions::quick_fixes(uri, params.range, &params.context.diagnostics, &text, &index));
+        }
+        if wanted(&CodeActionKind::SOURCE_ORGANIZE_IMPORTS)
+            && let Some(action) = self.with_current_ast(uri, |ast, text| code_actions::organize_imports(uri, ast, text, &index)).flatten() {
+            actions.push(action);
+        }
+        Ok((!actions.is_empty()).then_some(actions))
+    }
+
+    /// Une lentille au-dessus de chaque fonction et classe ; le nombre de références n'est
+    /// calculé qu'à la demande, dans `code_lens_resolve`.
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = &params.text_document.uri;
+        let lenses: Vec<CodeLens> = match self.symbols.read() {
+            Ok(read_guard) => read_guard
+                .get(uri)
+                .into_iter()
+                .flatten()
+                .filter(|s| matches!(s.kind, CompletionItemKind::FUNCTION | CompletionItemKind::CLASS))
+                .map(|s| CodeLens {
+                    range: s.range,
+                    command: None,
+                    data: Some(serde_json::json!({ "uri": uri, "name": s.name })),
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        Ok(Some(lenses))
+    }
+
+    async fn code_lens_resolve(&self, mut lens: CodeLens) -> Result<CodeLens> {
+        let target = lens.data.as_ref().and_then(|data| {
+            let uri = Url::parse(data.get("uri")?.as_str()?).ok()?;
+            Some((uri, data.get("name")?.as_str()?.to_string()))
+        });
+        let Some((uri, name)) = target else {
+            return Ok(lens);
+        };
+
+        let locations: Vec<Location> = self.uses(&uri, &name).into_iter().map(|range| Location { uri: uri.clone(), range }).collect();
+        let title = match locations.len() {
+            1 => "1 reference".to_string(),
+            n => format!("{} references", n),
+        };
+        // Commande de VS Code qui affiche les références sous la déclaration
+        lens.command = Some(Command {
+            title,
+            command: "editor.action.showReferences".to_string(),
+            arguments: Some(vec![serde_json::json!(uri), serde_json::json!(lens.range.start), serde_json::json!(locations)]),
+        });
+        Ok(lens)
+    }
+
+    async fn semantic_tokens_full(&self, params: SemanticTokensParams) -> Result<Option<SemanticTokensResult>> {
+        let uri = &params.text_document.uri;
+        if self.is_too_large(uri) {
+            return Ok(None);
+        }
+        let (Some(text), Some(index)) = (self.documents.get_text(uri), self.documents.line_index(uri)) else {
+            return Ok(None);
+        };
+
+        // Déclarations du document, puis celles des autres fichiers qu'il peut utiliser
+        let mut known = match self.symbols.read() {
+            Ok(read_guard) => read_guard.get(uri).cloned().unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        known.extend(self.workspace.exported_symbols(uri));
+
+        let data = semantic_tokens::semantic_tokens(&text, &index, self.documents.encoding(), &known);
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens { result_id: None, data })))
+    }
+
+    async fn prepare_rename(&self, params: TextDocumentPositionParams) -> Result<Option<PrepareRenameResponse>> {
+        let Some((word, range)) = self.documents.word_at(&params.text_document.uri, params.position) else {
+            return Ok(None);
+        };
+
+        Ok(self.is_declared(&params.text_document.uri, &word).then_some(PrepareRenameResponse::Range(range)))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let new_name = params.new_name;
+
+        if completion::KEYWORDS.contains(&new_name.as_str()) {
+            return Err(Error::invalid_params(format!("'{}' is a reserved keyword", new_name)));
+        }
+        if !is_identifier(&new_name) {
+            return Err(Error::invalid_params(format!("'{}' is not a valid identifier", new_name)));
+        }
+
+        let Some((word, _)) = self.documents.word_at(uri, position) else {
+            return Ok(None);
+        };
+        // Client qui n'a pas appelé `prepareRename` : même vérification
+        if !self.is_declared(uri, &word) {
+            return Ok(None);
+        }
+
+        let edits: Vec<TextEdit> = self
+            .occurrences(uri, &word)
+            .into_iter()
+            .map(|range| TextEdit { range, new_text: new_name.clone() })
+            .collect();
+        if edits.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), edits)])),
+            ..Default::default()
+        }))
+    }
+
+    async fn document_symbol(&self, params: DocumentSymbolParams) -> Result<Option<DocumentSymbolResponse>> {
+        let encoding = self.documents.encoding();
+        let tree = self.with_ast(&params.text_document.uri, |ast, text| symbols::symbol_tree(ast, text, encoding));
+
+        Ok(tree.map(|symbols| {
+            DocumentSymbolResponse::Nested(symbols.iter().map(SymbolInfo::document_symbol).collect())
+        }))
+    }
+
+    /// Recherche de symboles dans tout le projet : documents ouverts (symboles de leur
+    /// dernière version valide), puis fichiers indexés qui ne sont pas ouverts.
+    async fn symbol(&self, params: WorkspaceSymbolParams) -> Result<Option<Vec<SymbolInformation>>> {
+        let query = params.query;
+        let (mut found, open): (Vec<SymbolInformation>, HashSet<Url>) = match self.symbols.read() {
+            Ok(read_guard) => (
+                read_guard
+                    .iter()
+                    .flat_map(|(uri, symbols)| {
+                        symbols.iter().filter(|s| s.matches(&query)).map(move |s| s.symbol_information(uri))
+                    })
+                    .collect(),
+                read_guard.keys().cloned().collect(),
+            ),
+            Err(_) => (Vec::new(), HashSet::new()),
+        };
+        found.extend(self.workspace.search(&query, &open));
+
+        Ok(Some(found))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        Ok(self.with_ast(&params.text_document.uri, folding::folding_ranges))
+    }
+
+    async fn prepare_call_hierarchy(&self, params: CallHierarchyPrepareParams) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let Some((word, _)) = self.documents.word_at(uri, position) else {
+            return Ok(None);
+        };
+        let Ok(read_guard) = self.symbols.read() else {
+            return Ok(None);
+        };
+        let item = read_guard
+            .get(uri)
+            .and_then(|symbols| call_hierarchy::callable_at(symbols, &word, position))
+            .map(|symbol| symbol.call_hierarchy_item(uri));
+        Ok(item.map(|item| vec![item]))
+    }
+
+    async fn incoming_calls(&self, params: CallHierarchyIncomingCallsParams) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let item = &params.item;
+        let incoming = self.with_calls(&item.uri, |symbols, calls| {
+            let target = call_hierarchy::find_item(symbols, &item.name, item.selection_range)?;
+            // Un élément par appelant, avec tous ses appels
+            let mut incoming: Vec<CallHierarchyIncomingCall> = Vec::new();
+            for call in calls.iter().filter(|call| std::ptr::eq(call.callee, target)) {
+                let from = call.caller.call_hierarchy_item(&item.uri);
+                match incoming.iter_mut().find(|existing| existing.from == from) {
+                    Some(existing) => existing.from_ranges.push(call.range),
+                    None => incoming.push(CallHierarchyIncomingCall { from, from_ranges: vec![call.range] }),
                 }
+            }
+            Some(incoming)
+        });
+        Ok(incoming.flatten())
+    }
 
-                // Validation supplémentaire via le Loader (vérifie structure des blocs)
-                if let Err(e) = loader::parse_block(&json_ast) {
-                    diagnostics.push(self.parse_error_message(&e));
+    async fn outgoing_calls(&self, params: CallHierarchyOutgoingCallsParams) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let item = &params.item;
+        let outgoing = self.with_calls(&item.uri, |symbols, calls| {
+            let source = call_hierarchy::find_item(symbols, &item.name, item.selection_range)?;
+            let mut outgoing: Vec<CallHierarchyOutgoingCall> = Vec::new();
+            for call in calls.iter().filter(|call| std::ptr::eq(call.caller, source)) {
+                let to = call.callee.call_hierarchy_item(&item.uri);
+                match outgoing.iter_mut().find(|existing| existing.to == to) {
+                    Some(existing) => existing.from_ranges.push(call.range),
+                    None => outgoing.push(CallHierarchyOutgoingCall { to, from_ranges: vec![call.range] }),
                 }
-            },
-            Err(e) => {
-                // Erreur de parsing / compilation
-                diagnostics.push(self.parse_error_message(&e));
-                
-                // En cas d'erreur fatale, on vide la liste des symboles locaux 
-                // pour éviter de suggérer des choses obsolètes, ou on garde le cache précédent.
-                // Ici on choisit de ne rien faire (garder le cache précédent si le write lock n'est pas pris).
             }
+            Some(outgoing)
+        });
+        Ok(outgoing.flatten())
+    }
+
+    async fn selection_range(&self, params: SelectionRangeParams) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = &params.text_document.uri;
+        let (Some(text), Some(index)) = (self.documents.get_text(uri), self.documents.line_index(uri)) else {
+            return Ok(None);
+        };
+        // Document qui ne compile pas : sans les instructions
+        let ranges = self
+            .with_current_ast(uri, |ast, text| selection::selection_ranges(Some(ast), text, &index, &params.positions))
+            .unwrap_or_else(|| selection::selection_ranges(None, &text, &index, &params.positions));
+        Ok(Some(ranges))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = &params.text_document.uri;
+        if self.is_too_large(uri) {
+            return Ok(None);
         }
+        let (Some(text), Some(index)) = (self.documents.get_text(uri), self.documents.line_index(uri)) else {
+            return Ok(None);
+        };
 
-        self.client.publish_diagnostics(uri, diagnostics, None).await;
+        // Types : les positions doivent correspondre au texte affiché
+        let encoding = self.documents.encoding();
+        let mut hints = self
+            .with_current_ast(uri, |ast, text| inlay_hints::type_hints(ast, text, params.range, encoding))
+            .unwrap_or_default();
+
+        // Paramètres : fonctions et méthodes du document, puis celles des autres fichiers
+        let mut functions: Vec<SymbolInfo> = match self.symbols.read() {
+            Ok(read_guard) => read_guard.get(uri).cloned().unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        functions.extend(self.workspace.exported_symbols(uri));
+        hints.extend(inlay_hints::parameter_hints(&text, &index, params.range, &functions));
+
+        hints.sort_by_key(|hint| hint.position);
+        Ok(Some(hints))
     }
 
-    fn parse_error_message(&self, msg: &str) -> Diagnostic {
-        let mut line_num = 0;
-        
-        // Formats supportés : "(Line X)" ou "[Ligne X]" ou "[Line X]"
-        if let Some(start) = msg.find("(Line ") {
-            if let Some(end) = msg[start..].find(')') {
-                let num_str = &msg[start + 6 .. start + end];
-                if let Ok(n) = num_str.parse::<u32>() {
-                    line_num = n.saturating_sub(1);
-                }
+    async fn on_type_formatting(&self, params: DocumentOnTypeFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let (Some(text), Some(index)) = (self.documents.get_text(uri), self.documents.line_index(uri)) else {
+            return Ok(None);
+        };
+        let edit = on_type::closing_edit(&text, &index, params.text_document_position.position, &params.ch);
+        Ok(edit.map(|edit| vec![edit]))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri;
+        if self.is_too_large(&uri) {
+            return Ok(None);
+        }
+        // Un document qui ne compile pas n'est pas touché
+        let formatted = self.with_current_ast(&uri, |ast, text| {
+            formatting::format_document(ast, text, &params.options).map(|formatted| (formatted, text.to_string()))
+        });
+        let Some((formatted, text)) = formatted.flatten() else {
+            return Ok(None);
+        };
+        if formatted == text {
+            return Ok(Some(Vec::new()));
+        }
+
+        let end = LineIndex::new(&text, self.documents.encoding()).position(text.len());
+        Ok(Some(vec![TextEdit {
+            range: Range { start: Position::default(), end },
+            new_text: formatted,
+        }]))
+    }
+
+    /// Formatage des seules instructions entièrement sélectionnées.
+    async fn range_formatting(&self, params: DocumentRangeFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri;
+        if self.is_too_large(&uri) {
+            return Ok(None);
+        }
+        let encoding = self.documents.encoding();
+        let edits = self.with_current_ast(&uri, |ast, text| {
+            formatting::format_range(ast, text, params.range, &params.options, &LineIndex::new(text, encoding))
+        });
+        Ok(edits.flatten())
+    }
+
+    /// Diagnostics demandés par le client (LSP 3.17). Le rapport de la version courante est
+    /// réutilisé s'il existe ; si le client possède déjà ce rapport, il reçoit `Unchanged`.
+    async fn diagnostic(&self, params: DocumentDiagnosticParams) -> Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+        let Some((text, version)) = self.documents.snapshot(&uri) else {
+            return Ok(full_report(None, Vec::new()));
+        };
+
+        let cached = self.reports.read().ok().and_then(|reports| reports.get(&uri).cloned());
+        let report = match cached.filter(|report| report.version == Some(version)) {
+            Some(report) => report,
+            None => {
+                let token = self.current_token(&uri);
+                let Some(diagnostics) = self.analyze(&uri, &text, Some(version), &token).await else {
+                    return Err(Error::request_cancelled());
+                };
+                self.store_report(&uri, diagnostics, Some(version)).0
             }
-        } else if let Some(start) = msg.find("[Ligne ") {
-             if let Some(end) = msg[start..].find(']') {
-                let num_str = &msg[start + 7 .. start + end];
-                if let Ok(n) = num_str.parse::<u32>() {
-                    line_num = n.saturating_sub(1);
+        };
+
+        if params.previous_result_id.as_ref() == Some(&report.result_id) {
+            return Ok(DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(
+                RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                        result_id: report.result_id,
+                    },
+                },
+            )));
+        }
+        Ok(full_report(Some(report.result_id), report.diagnostics))
+    }
+
+    /// Diagnostics de tout le workspace : rapports des documents ouverts, et pour les autres
+    /// fichiers ceux calculés par l'indexation en tâche de fond (aucune compilation ici).
+    /// Envoyés par lots via `$/progress` si le client fournit un jeton de résultats partiels.
+    async fn workspace_diagnostic(&self, params: WorkspaceDiagnosticParams) -> Result<WorkspaceDiagnosticReportResult> {
+        let previous: HashMap<Url, String> =
+            params.previous_result_ids.into_iter().map(|p| (p.uri, p.value)).collect();
+
+        let open: HashSet<Url> = self.documents.uris().into_iter().collect();
+        let mut reports: Vec<(Url, Report)> = self
+            .reports
+            .read()
+            .map(|reports| reports.iter().map(|(uri, report)| (uri.clone(), report.clone())).collect())
+            .unwrap_or_default();
+        // Les rapports des documents ouverts sont déjà prêts pour le client, pas ceux de l'index
+        reports.extend(self.workspace.reports().into_iter().filter(|(uri, _)| !open.contains(uri)).map(|(uri, mut report)| {
+            report.diagnostics = self.finalize_diagnostics(report.diagnostics);
+            (uri, report)
+        }));
+
+        let items: Vec<WorkspaceDocumentDiagnosticReport> = reports
+            .into_iter()
+            .map(|(uri, report)| {
+                let version = report.version.map(i64::from);
+                if previous.get(&uri) == Some(&report.result_id) {
+                    WorkspaceDocumentDiagnosticReport::Unchanged(WorkspaceUnchangedDocumentDiagnosticReport {
+                        uri,
+                        version,
+                        unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                            result_id: report.result_id,
+                        },
+                    })
+                } else {
+                    WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                        uri,
+                        version,
+                        full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                            result_id: Some(report.result_id),
+                            items: report.diagnostics,
+                        },
+                    })
                 }
-            }
-        } else if let Some(start) = msg.find("[Line ") {
-             if let Some(end) = msg[start..].find(']') {
-                let num_str = &msg[start + 6 .. start + end];
-                if let Ok(n) = num_str.parse::<u32>() {
-                    line_num = n.saturating_sub(1);
+            })
+            .collect();
+
+        let Some(token) = params.partial_result_params.partial_result_token else {
+            return Ok(WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport { items }));
+        };
+        for batch in items.chunks(WORKSPACE_REPORT_BATCH) {
+            self.client
+                .send_notification::<WorkspaceDiagnosticProgress>(WorkspaceDiagnosticProgressParams {
+                    token: token.clone(),
+                    value: WorkspaceDiagnosticReportPartialResult { items: batch.to_vec() },
+                })
+                .await;
+        }
+        // Tout a été transmis par les envois partiels
+        Ok(WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport { items: Vec::new() }))
+    }
+
+    /// Commandes sur le document dont l'URI est le premier argument :
+    /// - `aegis.dumpAst` renvoie son AST JSON, indenté ;
+    /// - `aegis.compile` le compile puis le charge, et affiche la réussite ou l'erreur.
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        if !COMMANDS.contains(&params.command.as_str()) {
+            return Err(Error::invalid_params(format!("Unknown command '{}'", params.command)));
+        }
+        if params.command == VERSION_COMMAND {
+            let message = format!("Aegis LSP {}, compiler aegis-lang {}", env!("CARGO_PKG_VERSION"), COMPILER_VERSION);
+            self.client.show_message(MessageType::INFO, message.clone()).await;
+            return Ok(Some(Value::String(message)));
+        }
+        let Some(uri) = params.arguments.first().and_then(|v| v.as_str()).and_then(|s| Url::parse(s).ok()) else {
+            return Err(Error::invalid_params("Expected a document URI as first argument"));
+        };
+        let Some(text) = self.documents.get_text(&uri) else {
+            return Err(Error::invalid_params(format!("'{}' is not open", uri)));
+        };
+
+        let compiled = panics::catch(|| compiler::compile(&text));
+        if params.command == COMPILE_COMMAND {
+            let name = uri.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or(uri.as_str()).to_string();
+            let (kind, message) = match compiled {
+                Ok(Ok(ast)) => match panics::catch(|| loader::parse_block(&ast)) {
+                    Ok(Ok(_)) => (MessageType::INFO, format!("Aegis: {} compiled successfully", name)),
+                    Ok(Err(e)) => (MessageType::ERROR, format!("Aegis: {} failed to load: {}", name, e)),
+                    Err(panic) => (MessageType::ERROR, format!("Aegis: {} failed to load: {}", name, panic.message)),
+                },
+                Ok(Err(e)) => (MessageType::ERROR, format!("Aegis: {} failed to compile: {}", name, e)),
+                Err(panic) => (MessageType::ERROR, format!("Aegis: {} failed to compile: {}", name, panic.message)),
+            };
+            let success = kind == MessageType::INFO;
+            self.client.show_message(kind, message).await;
+            return Ok(Some(Value::Bool(success)));
+        }
+
+        match compiled {
+            Ok(Ok(ast)) => Ok(Some(Value::String(serde_json::to_string_pretty(&ast).unwrap_or_default()))),
+            Ok(Err(e)) => Err(Error::invalid_params(format!("Document does not compile: {}", e))),
+            Err(panic) => Err(Error::invalid_params(format!("internal compiler error: {}", panic.message))),
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Backend {
+    /// Indexe les dossiers en tâche de fond pour ne pas bloquer le client.
+    fn index_folders(&self, roots: Vec<PathBuf>) {
+        if roots.is_empty() {
+            return;
+        }
+
+        let index = Arc::clone(&self.workspace);
+        let backend = self.clone();
+        let encoding = self.documents.encoding();
+        let max_size = self.max_file_size();
+        tokio::spawn(async move {
+            let count = tokio::task::spawn_blocking(move || {
+                roots.iter().map(|root| index.index_folder(root, encoding, max_size)).sum::<usize>()
+            })
+            .await
+            .unwrap_or(0);
+
+            backend.client
+                .log_message(MessageType::INFO, format!("Aegis LSP: {} workspace file(s) indexed", count))
+                .await;
+            // Les diagnostics des fichiers non ouverts sont désormais disponibles
+            backend.refresh_diagnostics().await;
+        });
+    }
+
+    /// Remplace les réglages. Les documents ouverts sont revalidés si la limite de taille ou
+    /// les gravités des diagnostics changent.
+    async fn apply_settings(&self, settings: Settings) {
+        let previous_limit = self.max_file_size();
+        let limit_changed = settings.max_file_size() != previous_limit;
+        // Gravités, nombre maximal et langue : appliqués à la transmission de chaque rapport
+        let reports_changed = self.settings.read().is_ok_and(|previous| {
+            previous.lints != settings.lints
+                || previous.diagnostics != settings.diagnostics
+                || previous.max_diagnostics_per_file != settings.max_diagnostics_per_file
+                || previous.locale != settings.locale
+        });
+        let imports_changed = self.settings.read().is_ok_and(|previous| previous.import_paths != settings.import_paths);
+        if let Ok(mut write_guard) = self.settings.write() {
+            *write_guard = settings;
+        }
+        self.documents.set_max_cached_asts(self.max_cached_documents());
+
+        // Une nouvelle limite de taille peut (dés)activer l'analyse de fichiers déjà ouverts ou indexés
+        if limit_changed {
+            self.index_folders(self.workspace.folders());
+        }
+        if limit_changed || reports_changed || imports_changed {
+            for uri in self.documents.uris() {
+                if let Some((text, version)) = self.documents.snapshot(&uri) {
+                    let token = self.current_token(&uri);
+                    self.schedule_validation(uri, text, Some(version), token);
                 }
             }
         }
+        // Les rapports des fichiers non ouverts changent de gravités sans changer de contenu
+        if reports_changed {
+            self.workspace.renew_reports();
+            self.refresh_diagnostics().await;
+        }
+    }
 
-        Diagnostic {
-            range: Range {
-                start: Position { line: line_num, character: 0 },
-                end: Position { line: line_num, character: 100 },
+    /// Réglages lus dans `value` ; ceux qui sont invalides sont signalés dans le log.
+    async fn read_settings(&self, value: &Value) -> Settings {
+        let (settings, errors) = Settings::from_value(value);
+        for error in errors {
+            self.client.log_message(MessageType::WARNING, format!("Aegis LSP: invalid setting {}, default kept", error)).await;
+        }
+        settings
+    }
+
+    /// Section `aegis` demandée par `workspace/configuration`, si le client le permet.
+    async fn fetch_settings(&self) -> Option<Settings> {
+        let supported = self
+            .client_capabilities
+            .read()
+            .is_ok_and(|caps| caps.workspace.as_ref().and_then(|w| w.configuration) == Some(true));
+        if !supported {
+            return None;
+        }
+        let item = ConfigurationItem { scope_uri: None, section: Some("aegis".to_string()) };
+        match self.client.configuration(vec![item]).await {
+            Ok(values) => match values.first().filter(|v| !v.is_null()) {
+                Some(value) => Some(self.read_settings(value).await),
+                None => None,
+            },
+            Err(e) => {
+                self.client.log_message(MessageType::WARNING, format!("Aegis LSP: workspace/configuration failed: {}", e)).await;
+                None
             },
-            severity: Some(DiagnosticSeverity::ERROR),
-            source: Some("Aegis".to_string()),
-            message: msg.to_string(),
+        }
+    }
+
+    /// Demande au client de surveiller les fichiers `.aegis` (si l'enregistrement dynamique est supporté).
+    async fn register_file_watcher(&self) {
+        let supported = self.client_capabilities.read().is_ok_and(|caps| {
+            caps.workspace
+                .as_ref()
+                .and_then(|w| w.did_change_watched_files.as_ref())
+                .and_then(|w| w.dynamic_registration)
+                .unwrap_or(false)
+        });
+        if !supported {
+            return;
+        }
+
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.aegis".to_string()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "aegis-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+
+        if let Err(e) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(MessageType::WARNING, format!("Aegis LSP: file watcher registration failed: {}", e))
+                .await;
+        }
+    }
+
+    /// Applique `f` aux symboles du document et aux appels entre ses fonctions.
+    fn with_calls<R>(&self, uri: &Url, f: impl FnOnce(&[SymbolInfo], &[call_hierarchy::Call]) -> R) -> Option<R> {
+        let (text, index) = (self.documents.get_text(uri)?, self.documents.line_index(uri)?);
+        // Copie des symboles : le parcours du texte ne doit pas bloquer leur mise à jour
+        let symbols = self.document_symbols(uri)?;
+        let calls = call_hierarchy::calls(&symbols, &text, &index);
+        Some(f(&symbols, &calls))
+    }
+
+    /// Copie des symboles du document, prise sans garder le verrou.
+    fn document_symbols(&self, uri: &Url) -> Option<Vec<SymbolInfo>> {
+        self.symbols.read().ok()?.get(uri).cloned()
+    }
+
+    /// Membres de la classe de `receiver` : la classe elle-même, `this` dans un corps
+    /// de classe, ou une variable dont la déclaration indique la classe. Classe inconnue :
+    /// les membres de toutes les classes du document, classés après les autres éléments.
+    fn member_completions(&self, uri: &Url, receiver: &str, position: Position) -> Option<Vec<CompletionItem>> {
+        let symbols = self.document_symbols(uri)?;
+        let is_class = |s: &&SymbolInfo| s.kind == CompletionItemKind::CLASS;
+
+        let class = if receiver == "this" {
+            symbols.iter().filter(is_class).rfind(|s| s.encloses(position)).map(|s| s.name.clone())
+        } else if symbols.iter().filter(is_class).any(|s| s.name == receiver) {
+            Some(receiver.to_string())
+        } else {
+            symbols
+                .iter()
+                .filter(|s| s.name == receiver && s.visible_at(position))
+                .rev()
+                .find_map(|s| s.type_name.clone())
+        };
+
+        let snippets = self.snippet_support();
+        let Some(class) = class else {
+            let members: Vec<SymbolInfo> = symbols.iter().filter(|s| s.is_member()).cloned().collect();
+            let items: Vec<CompletionItem> = symbols::dedup(&members)
+                .into_iter()
+                .map(|s| CompletionItem { sort_text: Some(format!("~{}", s.name)), ..s.completion_item(snippets) })
+                .collect();
+            return (!items.is_empty()).then_some(items);
+        };
+        let members = symbols::class_members(&symbols, &class);
+        if members.is_empty() {
+            return None;
+        }
+        Some(members.into_iter().map(|s| s.completion_item(snippets)).collect())
+    }
+
+    /// Déclarations du namespace `path`, dans le document ou ailleurs dans le workspace.
+    fn namespace_completions(&self, uri: &Url, path: &str) -> Option<Vec<CompletionItem>> {
+        let mut symbols: Vec<SymbolInfo> = self
+            .symbols
+            .read()
+            .ok()
+            .and_then(|read_guard| read_guard.get(uri).cloned())
+            .unwrap_or_default();
+        symbols.extend(self.workspace.exported_symbols(uri));
+
+        let members: Vec<SymbolInfo> = symbols::namespace_members(&symbols, path).into_iter().cloned().collect();
+        if members.is_empty() {
+            return None;
+        }
+        let snippets = self.snippet_support();
+        Some(symbols::dedup(&members).into_iter().map(|s| s.completion_item(snippets)).collect())
+    }
+
+    /// Vrai si `word` est un symbole déclaré dans le document : seuls ceux-là peuvent être
+    /// renommés.
+    fn is_declared(&self, uri: &Url, word: &str) -> bool {
+        self.symbols.read().is_ok_and(|read_guard| read_guard.get(uri).is_some_and(|symbols| symbols.iter().any(|s| s.name == word)))
+    }
+
+    /// Plages de toutes les occurrences de l'identifiant `word` dans le document
+    /// (même nom, sans tenir compte du masquage).
+    fn occurrences(&self, uri: &Url, word: &str) -> Vec<Range> {
+        let (Some(text), Some(index)) = (self.documents.get_text(uri), self.documents.line_index(uri)) else {
+            return Vec::new();
+        };
+
+        references::identifier_offsets(&text, word)
+            .into_iter()
+            .map(|offset| Range { start: index.position(offset), end: index.position(offset + word.len()) })
+            .collect()
+    }
+
+    /// Occurrences de `word` hors de ses déclarations et affectations.
+    fn uses(&self, uri: &Url, word: &str) -> Vec<Range> {
+        let declarations: Vec<Range> = match self.symbols.read() {
+            Ok(read_guard) => read_guard
+                .get(uri)
+                .map(|symbols| symbols.iter().filter(|s| s.name == word).map(|s| s.range).collect())
+                .unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        self.occurrences(uri, word).into_iter().filter(|range| !declarations.contains(range)).collect()
+    }
+
+    fn max_file_size(&self) -> u64 {
+        self.settings.read().map(|s| s.max_file_size()).unwrap_or_else(|_| Settings::default().max_file_size())
+    }
+
+    /// Exécute `f` sur l'AST et le texte du document. L'AST en cache peut dater d'une
+    /// version antérieure (dernière version valide) ; un AST libéré par le cache est
+    /// recompilé depuis le texte courant.
+    fn with_ast<R>(&self, uri: &Url, f: impl FnOnce(&Value, &str) -> R) -> Option<R> {
+        self.cached_ast(uri, false, f)
+    }
+
+    /// Comme `with_ast`, mais l'AST correspond toujours au texte courant : un AST en
+    /// cache d'une version antérieure est remplacé, et un document qui ne compile pas
+    /// donne None.
+    fn with_current_ast<R>(&self, uri: &Url, f: impl FnOnce(&Value, &str) -> R) -> Option<R> {
+        self.cached_ast(uri, true, f)
+    }
+
+    fn cached_ast<R>(&self, uri: &Url, current: bool, f: impl FnOnce(&Value, &str) -> R) -> Option<R> {
+        // Copie sous le verrou (rope et AST), texte et traitement une fois le verrou rendu
+        let (rope, version, cached) = self.documents.with_document(uri, |doc| {
+            let usable = !current || doc.ast_version == Some(doc.version);
+            (doc.rope.clone(), doc.version, doc.ast.clone().filter(|_| usable))
+        })?;
+        let text = rope.to_string();
+        if let Some(ast) = cached {
+            return Some(f(&ast, &text));
+        }
+
+        if self.is_too_large(uri) {
+            return None;
+        }
+        let Ok(Ok(ast)) = panics::catch(|| compiler::compile(&text)) else {
+            return None;
+        };
+        let result = f(&ast, &text);
+        self.documents.set_ast(uri, Some(ast), Some(version));
+        Some(result)
+    }
+
+    fn max_cached_documents(&self) -> usize {
+        self.settings.read().map(|s| s.max_cached_documents).unwrap_or_else(|_| Settings::default().max_cached_documents)
+    }
+
+    /// Requête `aegis/memory` : taille des caches, pour affichage par l'extension.
+    async fn memory(&self) -> Result<MemoryStats> {
+        let (open_documents, cached_asts) = self.documents.counts();
+        Ok(MemoryStats {
+            open_documents,
+            cached_asts,
+            max_cached_documents: self.max_cached_documents(),
+            symbol_lists: self.symbols.read().map(|s| s.len()).unwrap_or_default(),
+            workspace_files: self.workspace.file_count(),
+        })
+    }
+
+    /// Minutes depuis lesquelles le document ne compile plus, au-delà de `STALE_SYMBOLS`.
+    fn stale_minutes(&self, uri: &Url) -> Option<u64> {
+        let since = self.failing_since.read().ok()?.get(uri).copied()?;
+        let elapsed = since.elapsed();
+        (elapsed >= STALE_SYMBOLS).then_some(elapsed.as_secs() / 60)
+    }
+
+    fn is_too_large(&self, uri: &Url) -> bool {
+        self.documents.len_bytes(uri).is_some_and(|len| len as u64 > self.max_file_size())
+    }
+
+    /// Fichier trop volumineux : on oublie ses symboles et on l'indique par un diagnostic unique.
+    fn disable_analysis(&self, uri: &Url, size: usize) -> Diagnostic {
+        if let Ok(mut write_guard) = self.symbols.write() {
+            write_guard.remove(uri);
+        }
+        if let Ok(mut failing) = self.failing_since.write() {
+            failing.remove(uri);
+        }
+        self.workspace.remove(uri);
+        self.documents.set_ast(uri, None, None);
+
+        let limit = self.settings.read().map(|s| s.max_file_size_kb).unwrap_or_default();
+        let code = DiagnosticCode::Disabled;
+        Diagnostic {
+            range: Range::default(),
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            code: Some(NumberOrString::String(code.as_str().to_string())),
+            code_description: code.description(),
+            source: Some(code.source().to_string()),
+            message: format!(
+                "Analysis disabled for large files ({} KB, limit aegis.maxFileSizeKb = {} KB)",
+                size.div_ceil(1024),
+                limit
+            ),
             ..Default::default()
         }
     }
 
-    fn extract_symbols(&self, ast: &Value) -> Vec<CompletionItem> {
-        let mut symbols = Vec::new();
+    fn diagnostics_debounce(&self) -> Duration {
+        self.settings.read().map(|s| s.diagnostics_debounce()).unwrap_or_else(|_| Settings::default().diagnostics_debounce())
+    }
 
-        if let Some(arr) = ast.as_array() {
-            if !arr.is_empty() && arr[0].is_string() {
-                self.analyze_instruction(arr, &mut symbols);
-            } else {
-                for item in arr {
-                    let sub_symbols = self.extract_symbols(item);
-                    symbols.extend(sub_symbols);
-                }
+    fn validate_on(&self) -> ValidateOn {
+        self.settings.read().map(|s| s.validate_on).unwrap_or_default()
+    }
+
+    /// Confie la validation à la tâche du document : les validations d'un même document
+    /// s'exécutent dans l'ordre, celles de documents différents en parallèle.
+    fn schedule_validation(&self, uri: Url, text: String, version: Option<i32>, token: CancellationToken) {
+        let Ok(mut workers) = self.validations.write() else {
+            return;
+        };
+
+        let mut validation = Validation { text, version, token };
+        if let Some(sender) = workers.get(&uri) {
+            match sender.send(validation) {
+                Ok(()) => return,
+                // Tâche terminée : on en relance une avec la même demande
+                Err(mpsc::error::SendError(returned)) => validation = returned,
             }
         }
 
-        symbols
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let _ = sender.send(validation);
+        tokio::spawn(self.clone().run_validations(uri.clone(), receiver));
+        workers.insert(uri, sender);
     }
 
-    fn analyze_instruction(&self, arr: &Vec<Value>, symbols: &mut Vec<CompletionItem>) {
-        if arr.is_empty() { return; }
-        
-        let cmd = arr[0].as_str().unwrap_or(""); 
-        
-        match cmd {
-            // --- VARIABLES & CONSTANTES ---
-            "var" | "set" => {
-                // ["var", line, "nom", type, expr]
-                if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
-                    symbols.push(CompletionItem {
-                        label: name.to_string(),
-                        kind: Some(CompletionItemKind::VARIABLE),
-                        detail: Some("Variable".to_string()),
-                        ..Default::default()
-                    });
-                }
-            },
-            "const" => {
-                // ["const", line, "nom", expr]
-                if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
-                    symbols.push(CompletionItem {
-                        label: name.to_string(),
-                        kind: Some(CompletionItemKind::CONSTANT),
-                        detail: Some("Constant".to_string()),
-                        ..Default::default()
-                    });
-                }
-            },
+    /// Boucle de la tâche d'un document. Les demandes arrivées pendant une validation
+    /// sont fusionnées : seule la plus récente est validée ensuite.
+    async fn run_validations(self, uri: Url, mut receiver: mpsc::UnboundedReceiver<Validation>) {
+        while let Some(mut validation) = receiver.recv().await {
+            while let Ok(newer) = receiver.try_recv() {
+                validation = newer;
+            }
+            if !validation.token.is_cancelled() {
+                self.validate_document(uri.clone(), validation.text, validation.version, &validation.token).await;
+            }
+        }
+    }
 
-            // --- FONCTIONS ---
-            "function" | "func" => {
-                // ["function", line, "nom", params, ret, body]
-                if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
-                    symbols.push(CompletionItem {
-                        label: name.to_string(),
-                        kind: Some(CompletionItemKind::FUNCTION),
-                        detail: Some("Function".to_string()),
-                        insert_text: Some(format!("{}($0)", name)),
-                        insert_text_format: Some(InsertTextFormat::SNIPPET),
-                        ..Default::default()
-                    });
-                }
-                // Récursion dans le corps (index 5 habituellement)
-                if let Some(body) = arr.get(5) {
-                    self.extract_symbols(body).into_iter().for_each(|s| symbols.push(s));
-                }
-            },
+    /// Annule le jeton de la version précédente du document et en crée un nouveau.
+    fn renew_token(&self, uri: &Url) -> CancellationToken {
+        let token = CancellationToken::new();
+        if let Ok(mut tokens) = self.cancellations.write()
+            && let Some(previous) = tokens.insert(uri.clone(), token.clone()) {
+            previous.cancel();
+        }
+        token
+    }
 
-            // --- CLASSES (Nouvelle syntaxe avec objet de définition) ---
-            "class" => {
-                // Format possible 1 : ["class", line, "Name", ...] (Vieux)
-                // Format possible 2 : ["class", line, { "name": "Name", ... }] (Nouveau struct)
-                
-                let class_name = if let Some(n) = arr.get(2).and_then(|v| v.as_str()) {
-                    Some(n)
-                } else if let Some(obj) = arr.get(2).and_then(|v| v.as_object()) {
-                    obj.get("name").and_then(|v| v.as_str())
-                } else {
-                    None
-                };
+    /// Jeton de la version courante du document (le contenu n'a pas changé).
+    fn current_token(&self, uri: &Url) -> CancellationToken {
+        let existing = self.cancellations.read().ok().and_then(|tokens| tokens.get(uri).cloned());
+        existing.unwrap_or_else(|| self.renew_token(uri))
+    }
 
-                if let Some(name) = class_name {
-                    symbols.push(CompletionItem {
-                        label: name.to_string(),
-                        kind: Some(CompletionItemKind::CLASS),
-                        detail: Some("Class".to_string()),
-                        ..Default::default()
-                    });
-                }
-                
-                // Note: L'extraction des méthodes à l'intérieur de la classe nécessiterait
-                // de parser l'objet de définition de classe. Pour l'instant, on se contente du nom de la classe.
-            },
+    /// Ferme la file du document : sa tâche s'arrête une fois la validation en cours terminée.
+    fn stop_validations(&self, uri: &Url) {
+        if let Ok(mut workers) = self.validations.write() {
+            workers.remove(uri);
+        }
+    }
 
-            // --- INTERFACES ---
-            "interface" => {
-                // ["interface", line, { "name": "IParams", ... }] ou ["interface", line, "Name", ...]
-                let iface_name = if let Some(n) = arr.get(2).and_then(|v| v.as_str()) {
-                    Some(n)
-                } else if let Some(obj) = arr.get(2).and_then(|v| v.as_object()) {
-                    obj.get("name").and_then(|v| v.as_str())
-                } else {
-                    None
-                };
+    /// Vrai si aucune version plus récente du document n'est arrivée entre-temps.
+    fn is_current(&self, uri: &Url, version: Option<i32>) -> bool {
+        match version {
+            Some(v) => self.documents.version(uri).is_none_or(|current| current == v),
+            None => true,
+        }
+    }
 
-                if let Some(name) = iface_name {
-                    symbols.push(CompletionItem {
-                        label: name.to_string(),
-                        kind: Some(CompletionItemKind::INTERFACE),
-                        detail: Some("Interface".to_string()),
-                        ..Default::default()
-                    });
-                }
-            },
+    /// Compile le document et publie ses diagnostics. Une validation annulée entre-temps
+    /// (nouvelle modification, fermeture) s'arrête sans publier.
+    async fn validate_document(&self, uri: Url, text: String, version: Option<i32>, token: &CancellationToken) {
+        let Some(diagnostics) = self.analyze(&uri, &text, version, token).await else {
+            return;
+        };
 
-            // --- NAMESPACES ---
-            "namespace" => {
-                if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
-                    symbols.push(CompletionItem {
-                        label: name.to_string(),
-                        kind: Some(CompletionItemKind::MODULE),
-                        detail: Some("Namespace".to_string()),
-                        ..Default::default()
-                    });
-                }
-                if let Some(body) = arr.get(3) {
-                    self.extract_symbols(body).into_iter().for_each(|s| symbols.push(s));
+        // Un résultat obsolète ne doit pas écraser les diagnostics d'une version plus récente
+        if token.is_cancelled() || !self.is_current(&uri, version) {
+            return;
+        }
+
+        self.publish(uri, diagnostics, version).await;
+    }
+
+    /// Analyse du document sur un thread bloquant. Une panique du compilateur ou du loader
+    /// n'arrête que cette analyse : elle devient un diagnostic, et sa trace part dans le log.
+    async fn analyze(&self, uri: &Url, text: &str, version: Option<i32>, token: &CancellationToken) -> Option<Vec<Diagnostic>> {
+        let backend = self.clone();
+        let (task_uri, text, task_token) = (uri.clone(), text.to_string(), token.clone());
+        let analyzed = tokio::task::spawn_blocking(move || {
+            panics::catch(|| backend.run_analysis(&task_uri, &text, version, &task_token))
+        })
+        .await
+        .ok()?;
+
+        match analyzed {
+            Ok(analysis) => {
+                let analysis = analysis?;
+                for message in &analysis.unlocated {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("Aegis LSP: no position in diagnostic for {}: {}", uri, message))
+                        .await;
                 }
+                Some(analysis.diagnostics)
             },
+            Err(panic) => {
+                let backtrace = panic.backtrace.map(|b| b.to_string()).unwrap_or_default();
+                self.client
+                    .log_message(MessageType::ERROR, format!("Aegis LSP: compiler panicked on {}: {}\n{}", uri, panic.message, backtrace))
+                    .await;
+                Some(vec![internal_error(&panic.message)])
+            },
+        }
+    }
 
-            // --- FOREACH (Nouveau) ---
-            "foreach" => {
-                // ["foreach", line, "var_iter", iterable, body]
-                if let Some(var_name) = arr.get(2).and_then(|v| v.as_str()) {
-                    symbols.push(CompletionItem {
-                        label: var_name.to_string(),
-                        kind: Some(CompletionItemKind::VARIABLE),
-                        detail: Some("Iterator".to_string()),
-                        ..Default::default()
-                    });
+    /// Diagnostics du texte `text` (version `version` du document), avec mise à jour des
+    /// symboles et de l'AST en cache. None si la validation a été annulée entre-temps :
+    /// symboles et cache restent alors intacts.
+    fn run_analysis(&self, uri: &Url, text: &str, version: Option<i32>, token: &CancellationToken) -> Option<Analysis> {
+        if text.len() as u64 > self.max_file_size() {
+            return Some(Analysis { diagnostics: vec![self.disable_analysis(uri, text.len())], unlocated: Vec::new() });
+        }
+
+        // On utilise le compilateur Aegis
+        let compiled = compiler::compile(text);
+        if token.is_cancelled() {
+            return None;
+        }
+
+        let index = LineIndex::new(text, self.documents.encoding());
+        let found_symbols = match &compiled {
+            Ok(json_ast) => {
+                // Extraction des symboles pour l'autocomplétion
+                let found_symbols = symbols::extract_symbols(json_ast, text, self.documents.encoding());
+                self.workspace.update(uri.clone(), found_symbols.clone());
+                if let Ok(mut write_guard) = self.symbols.write() {
+                    write_guard.insert(uri.clone(), found_symbols.clone());
                 }
-                // Récursion dans le corps (index 4)
-                if let Some(body) = arr.get(4) {
-                    self.extract_symbols(body).into_iter().for_each(|s| symbols.push(s));
+                self.documents.set_ast(uri, Some(json_ast.clone()), version);
+                if let Ok(mut failing) = self.failing_since.write() {
+                    failing.remove(uri);
                 }
+                found_symbols
             },
-            
-            // --- BLOCS DE RÉCURSION ---
-            "if" => {
-                // ["if", line, cond, then, else]
-                if let Some(then_block) = arr.get(3) {
-                    self.extract_symbols(then_block).into_iter().for_each(|s| symbols.push(s));
+            // Erreur de parsing / compilation : on garde le cache de symboles précédent, ou à
+            // défaut ceux du contenu sur disque si l'index les connaît
+            Err(_) => {
+                if let Ok(mut write_guard) = self.symbols.write()
+                    && !write_guard.contains_key(uri)
+                    && let Some(previous) = self.workspace.symbols_of(uri) {
+                    write_guard.insert(uri.clone(), previous);
                 }
-                if let Some(else_block) = arr.get(4) {
-                    self.extract_symbols(else_block).into_iter().for_each(|s| symbols.push(s));
+                if let Ok(mut failing) = self.failing_since.write() {
+                    failing.entry(uri.clone()).or_insert_with(Instant::now);
                 }
+                Vec::new()
             },
-            "while" => {
-                // ["while", line, cond, body]
-                if let Some(body) = arr.get(3) {
-                    self.extract_symbols(body).into_iter().for_each(|s| symbols.push(s));
-                }
-            },
-            "try_catch" | "try" => {
-                // ["try_catch", line, try_body, err_var, catch_body]
-                if let Some(try_body) = arr.get(2) {
-                    self.extract_symbols(try_body).into_iter().for_each(|s| symbols.push(s));
-                }
-                // Ajout de la variable d'erreur au scope
-                if let Some(err_var) = arr.get(3).and_then(|v| v.as_str()) {
-                    symbols.push(CompletionItem {
-                        label: err_var.to_string(),
-                        kind: Some(CompletionItemKind::VARIABLE),
-                        detail: Some("Error Variable".to_string()),
-                        ..Default::default()
-                    });
-                }
-                if let Some(catch_body) = arr.get(4) {
-                    self.extract_symbols(catch_body).into_iter().for_each(|s| symbols.push(s));
+        };
+
+        let external = compiled.as_ref().ok().and_then(|ast| self.workspace.imported_names(ast));
+        let imported = compiled.as_ref().map(|ast| self.workspace.imported_symbols(ast)).unwrap_or_default();
+        let mut errors = diagnostics::check(text, &compiled, &found_symbols, external.as_ref(), &imported, &index);
+        if let Ok(ast) = &compiled {
+            errors.extend(self.import_diagnostics(uri, ast, text, &index));
+            if self.settings.read().is_ok_and(|s| s.lints.shadowing) {
+                errors.extend(
+                    shadowing::shadowed_declarations(ast)
+                        .iter()
+                        .filter_map(|declaration| diagnostics::shadowing(declaration, text, &index)),
+                );
+            }
+        }
+        let unlocated = errors.iter().filter(|e| e.unlocated).map(|e| e.message.clone()).collect();
+        let diagnostics = self.finalize_diagnostics(errors.iter().map(|e| e.to_lsp(uri, text, &index)).collect());
+        Some(Analysis { diagnostics, unlocated })
+    }
+
+    /// Imports de `ast` introuvables ou dont le fichier ne compile pas. Les chemins relatifs
+    /// partent du dossier du document, puis des racines du workspace et des `importPaths`.
+    fn import_diagnostics(&self, uri: &Url, ast: &Value, text: &str, index: &LineIndex) -> Vec<diagnostics::AegisDiagnostic> {
+        let imports = imports::imports(ast);
+        let Ok(path) = uri.to_file_path() else {
+            return imports
+                .into_iter()
+                .map(|(line, import)| diagnostics::unresolved_import(import, line, false, text, index))
+                .collect();
+        };
+        let document_dir = path.parent().unwrap_or(&path);
+        let search_paths = self.import_search_paths();
+
+        imports
+            .into_iter()
+            .filter_map(|(line, import)| match self.imports.resolve(import, document_dir, &search_paths) {
+                ImportStatus::Missing => Some(diagnostics::unresolved_import(import, line, true, text, index)),
+                ImportStatus::Broken(error) => Some(diagnostics::broken_import(import, line, &error, text, index)),
+                ImportStatus::Valid => None,
+            })
+            .collect()
+    }
+
+    /// Dossiers où chercher les imports après celui du document : racines du workspace,
+    /// puis `importPaths` (les chemins relatifs partent de chaque racine).
+    fn import_search_paths(&self) -> Vec<PathBuf> {
+        let folders = self.workspace.folders();
+        let configured = self.settings.read().map(|s| s.import_paths.clone()).unwrap_or_default();
+        let mut search_paths = folders.clone();
+        for configured in configured {
+            if configured.is_absolute() {
+                search_paths.push(configured);
+            } else {
+                search_paths.extend(folders.iter().map(|folder| folder.join(&configured)));
+            }
+        }
+        search_paths
+    }
+
+    /// Déclarations de `name` dans les fichiers que le document importe, résolus comme pour
+    /// les diagnostics d'import. Un fichier importé hors du workspace est lu depuis le disque.
+    fn imported_definitions(&self, uri: &Url, name: &str) -> Vec<Location> {
+        let Ok(path) = uri.to_file_path() else {
+            return Vec::new();
+        };
+        let document_dir = path.parent().unwrap_or(&path);
+        let Some(imports) = self.with_ast(uri, |ast, _| {
+            imports::imports(ast).into_iter().map(|(_, import)| import.to_string()).collect::<Vec<_>>()
+        }) else {
+            return Vec::new();
+        };
+
+        let search_paths = self.import_search_paths();
+        imports
+            .iter()
+            .filter_map(|import| self.imports.resolve_path(import, document_dir, &search_paths))
+            .filter_map(|target| {
+                let target_uri = Url::from_file_path(&target).ok()?;
+                self.workspace.definitions_in(name, &target_uri).or_else(|| self.definitions_on_disk(name, &target, &target_uri))
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Déclarations de `name` dans le fichier `path`, compilé depuis le disque sans être indexé.
+    fn definitions_on_disk(&self, name: &str, path: &Path, uri: &Url) -> Option<Vec<Location>> {
+        if fs::metadata(path).ok()?.len() > self.max_file_size() {
+            return None;
+        }
+        let text = fs::read_to_string(path).ok()?;
+        let ast = panics::catch(|| compiler::compile(&text)).ok()?.ok()?;
+        let symbols = symbols::extract_symbols(&ast, &text, self.documents.encoding());
+        Some(
+            symbols
+                .into_iter()
+                .filter(|s| s.name == name && workspace::is_exported(s.kind))
+                .map(|s| Location { uri: uri.clone(), range: s.range })
+                .collect(),
+        )
+    }
+
+    /// Diagnostics tels que le client les reçoit, qu'ils soient publiés ou demandés : avec
+    /// les gravités de `aegis.lints` et `aegis.diagnostics`, sans doublons, au plus
+    /// `maxDiagnosticsPerFile`, sans les étiquettes (code inutile, déprécié) absentes de son
+    /// `publishDiagnostics.tagSupport`, dans la langue de `aegis.locale`.
+    fn finalize_diagnostics(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let (max, levels, locale) = self
+            .settings
+            .read()
+            .map(|s| (s.max_diagnostics_per_file, (s.lints.levels.clone(), s.diagnostics.clone()), s.locale))
+            .unwrap_or_else(|_| (Settings::default().max_diagnostics_per_file, Default::default(), Locale::default()));
+        let diagnostics = diagnostics::apply_levels(diagnostics, &levels.0, &levels.1);
+        let diagnostics = diagnostics::dedupe(diagnostics);
+        let mut diagnostics = diagnostics::truncate(diagnostics, max);
+        let supported: Vec<DiagnosticTag> = self
+            .client_capabilities
+            .read()
+            .ok()
+            .and_then(|caps| caps.text_document.as_ref()?.publish_diagnostics.as_ref()?.tag_support.clone())
+            .map(|support| support.value_set)
+            .unwrap_or_default();
+        for diagnostic in &mut diagnostics {
+            if let Some(tags) = &mut diagnostic.tags {
+                tags.retain(|tag| supported.contains(tag));
+                if tags.is_empty() {
+                    diagnostic.tags = None;
                 }
-            },
-            
-            _ => {}
+            }
+            diagnostic.message = i18n::localize(&diagnostic.message, locale);
+            for related in diagnostic.related_information.iter_mut().flatten() {
+                related.message = i18n::localize(&related.message, locale);
+            }
+        }
+        diagnostics
+    }
+
+    /// Vrai si le client accepte les snippets (`$0`...) dans les complétions.
+    fn snippet_support(&self) -> bool {
+        self.client_capabilities.read().is_ok_and(|caps| {
+            caps.text_document
+                .as_ref()
+                .and_then(|t| t.completion.as_ref())
+                .and_then(|c| c.completion_item.as_ref())
+                .and_then(|i| i.snippet_support)
+                == Some(true)
+        })
+    }
+
+    /// Vrai si le client demande lui-même les diagnostics (`textDocument/diagnostic`) :
+    /// on ne les lui pousse alors plus.
+    fn pulls_diagnostics(&self) -> bool {
+        self.client_capabilities
+            .read()
+            .is_ok_and(|caps| caps.text_document.as_ref().is_some_and(|t| t.diagnostic.is_some()))
+    }
+
+    /// Garde les diagnostics de la version `version` pour les requêtes du client. Un rapport
+    /// identique à celui déjà gardé pour cette version conserve son identifiant ; le booléen
+    /// indique si le rapport a changé.
+    fn store_report(&self, uri: &Url, diagnostics: Vec<Diagnostic>, version: Option<i32>) -> (Report, bool) {
+        let Ok(mut reports) = self.reports.write() else {
+            return (Report::new(version, diagnostics), true);
+        };
+        if let Some(previous) = reports.get(uri)
+            && previous.version == version
+            && previous.diagnostics == diagnostics {
+            return (previous.clone(), false);
+        }
+
+        let report = Report::new(version, diagnostics);
+        reports.insert(uri.clone(), report.clone());
+        (report, true)
+    }
+
+    /// Transmet les diagnostics au client : publiés directement, ou, si le client les
+    /// demande lui-même, gardés pour sa prochaine requête qu'il est invité à relancer.
+    /// En mode push, une liste identique à la dernière publiée n'est pas renvoyée (sauf la
+    /// liste vide, toujours transmise).
+    async fn publish(&self, uri: Url, diagnostics: Vec<Diagnostic>, version: Option<i32>) {
+        if !self.pulls_diagnostics() {
+            if diagnostics.is_empty() {
+                self.clear_published(uri).await;
+                return;
+            }
+            let mut hasher = DefaultHasher::new();
+            serde_json::to_string(&diagnostics).unwrap_or_default().hash(&mut hasher);
+            let fingerprint = hasher.finish();
+            if let Ok(mut published) = self.published.write()
+                && published.insert(uri.clone(), fingerprint) == Some(fingerprint) {
+                return;
+            }
+            self.client.publish_diagnostics(uri, diagnostics, version).await;
+            return;
+        }
+
+        let (_, changed) = self.store_report(&uri, diagnostics, version);
+        if changed {
+            self.refresh_diagnostics().await;
+        }
+    }
+
+    /// Efface les diagnostics publiés pour `uri` : une liste vide est publiée explicitement
+    /// (document devenu valide, fermé ou supprimé), jamais sautée.
+    async fn clear_published(&self, uri: Url) {
+        if let Ok(mut published) = self.published.write() {
+            published.remove(&uri);
+        }
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+    }
+
+    /// Invite le client à redemander les diagnostics, s'il le permet.
+    async fn refresh_diagnostics(&self) {
+        let supported = self.client_capabilities.read().is_ok_and(|caps| {
+            caps.workspace.as_ref().and_then(|w| w.diagnostic.as_ref()).and_then(|d| d.refresh_support) == Some(true)
+        });
+        if supported
+            && let Err(e) = self.client.workspace_diagnostic_refresh().await {
+            self.client.log_message(MessageType::WARNING, format!("Aegis LSP: diagnostic refresh failed: {}", e)).await;
         }
     }
 }
 
+/// Panique du compilateur pendant l'analyse, signalée en tête du document.
+fn internal_error(message: &str) -> Diagnostic {
+    let code = DiagnosticCode::Internal;
+    Diagnostic {
+        range: Range::default(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(code.as_str().to_string())),
+        code_description: code.description(),
+        source: Some(code.source().to_string()),
+        message: format!("internal compiler error: {}", message),
+        ..Default::default()
+    }
+}
+
+/// Rapport complet de `textDocument/diagnostic`.
+fn full_report(result_id: Option<String>, items: Vec<Diagnostic>) -> DocumentDiagnosticReportResult {
+    DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+        related_documents: None,
+        full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id, items },
+    }))
+}
+
+/// Vrai si `name` peut servir de nom de variable ou de fonction.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_') && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
 #[tokio::main]
 async fn main() {
+    panics::install_hook();
+
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| Backend { 
+    let (service, socket) = LspService::build(|client| Backend {
         client,
-        symbols: RwLock::new(Vec::new())
-    });
+        symbols: Arc::new(RwLock::new(HashMap::new())),
+        documents: Arc::new(DocumentStore::default()),
+        settings: Arc::new(RwLock::new(Settings::default())),
+        workspace: Arc::new(WorkspaceIndex::default()),
+        imports: Arc::new(ImportResolver::default()),
+        client_capabilities: Arc::new(RwLock::new(ClientCapabilities::default())),
+        validations: Arc::new(RwLock::new(HashMap::new())),
+        cancellations: Arc::new(RwLock::new(HashMap::new())),
+        reports: Arc::new(RwLock::new(HashMap::new())),
+        failing_since: Arc::new(RwLock::new(HashMap::new())),
+        published: Arc::new(RwLock::new(HashMap::new())),
+    })
+    .custom_method("aegis/memory", Backend::memory)
+    .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 }
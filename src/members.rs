@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+
+use crate::undefined;
+
+/// Appel d'une méthode absente de la classe de l'instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownMember<'a> {
+    // Ligne (base 0) de l'instruction qui contient l'appel
+    pub line: u32,
+    pub class: &'a str,
+    pub name: &'a str,
+    // Rang de l'appel parmi ceux de `.name(` dans la même instruction (0 pour le premier)
+    pub occurrence: usize,
+    // Au plus trois membres de la classe aux noms les plus proches
+    pub suggestions: Vec<&'a str>,
+}
+
+/// Appels `x.nom(...)` où `x` est toujours affectée par `new Classe(...)`, d'une classe du
+/// document dont aucun membre (méthode, champ, attribut `this.a = ...`, hérité compris) ne
+/// s'appelle `nom`. Par prudence, une variable affectée autrement, aussi déclarée comme
+/// paramètre ou itérateur, ou dont un attribut est affecté, n'a pas de classe connue ; une
+/// classe qui hérite d'une classe d'un autre fichier, déclarée deux fois ou dans un
+/// namespace, non plus.
+pub fn unknown_members(ast: &Value) -> Vec<UnknownMember<'_>> {
+    let mut declarations = Declarations::default();
+    declarations.visit(ast, false);
+
+    let classes: HashMap<&str, HashSet<&str>> = declarations
+        .classes
+        .keys()
+        .filter_map(|&class| Some((class, declarations.members(class, &mut HashSet::new())?)))
+        .collect();
+    let instances: HashMap<&str, &str> = declarations
+        .bindings
+        .iter()
+        .filter(|(name, _)| !declarations.untyped.contains(*name))
+        .filter_map(|(&name, classes)| match classes.as_slice() {
+            [Some(class), rest @ ..] if rest.iter().all(|c| *c == Some(*class)) => Some((name, *class)),
+            _ => None,
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    calls(ast, 0, &instances, &classes, &mut HashMap::new(), &mut found);
+    found
+}
+
+/// Membres propres d'une classe et nom de sa classe parente.
+type Class<'a> = (HashSet<&'a str>, Option<&'a str>);
+
+#[derive(Default)]
+struct Declarations<'a> {
+    // Chaque classe du document ; None si ses membres ne sont pas fiables
+    classes: HashMap<&'a str, Option<Class<'a>>>,
+    // Classe instanciée par chaque affectation d'une variable (None : autre valeur)
+    bindings: HashMap<&'a str, Vec<Option<&'a str>>>,
+    untyped: HashSet<&'a str>,
+}
+
+impl<'a> Declarations<'a> {
+    fn visit(&mut self, node: &'a Value, in_namespace: bool) {
+        match node {
+            Value::Array(arr) => {
+                let cmd = arr.first().and_then(|v| v.as_str());
+                let name = arr.get(2).and_then(|v| v.as_str());
+                match cmd {
+                    // ["class", line, "nom", {méthode: [params, body, ...]}, parent, [champs], ...]
+                    Some("class") => {
+                        if let Some(name) = name {
+                            let members = (!in_namespace && !self.classes.contains_key(name)).then(|| {
+                                (class_members(arr), arr.get(4).and_then(|v| v.as_str()))
+                            });
+                            self.classes.insert(name, members);
+                        }
+                        for method in arr.get(3).and_then(|v| v.as_object()).into_iter().flat_map(|m| m.values()) {
+                            self.params(method.get(0));
+                        }
+                    },
+                    // ["set", line, "nom", type, expr], ["const", line, "nom", expr]
+                    Some("set" | "const") => {
+                        if let Some(name) = name {
+                            self.bindings.entry(name).or_default().push(instantiated(arr.last()));
+                        }
+                    },
+                    Some("function") => self.params(arr.get(3)),
+                    Some("lambda") => self.params(arr.get(1)),
+                    Some("foreach") => self.untyped.extend(name),
+                    // ["try", line, body, err_var, catch_body]
+                    Some("try") => self.untyped.extend(arr.get(3).and_then(|v| v.as_str())),
+                    // ["set_attr", line, ["get", "x"], "attribut", expr]
+                    Some("set_attr") => {
+                        if let Some(receiver) = arr.get(2).and_then(read_name) {
+                            self.untyped.insert(receiver);
+                        }
+                    },
+                    _ => {}
+                }
+
+                let in_namespace = in_namespace || cmd == Some("namespace");
+                for item in arr {
+                    self.visit(item, in_namespace);
+                }
+            },
+            Value::Object(map) => {
+                for item in map.values() {
+                    self.visit(item, in_namespace);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Paramètres `[["a", null], ...]`, ou `["a", ...]` pour une lambda.
+    fn params(&mut self, params: Option<&'a Value>) {
+        for param in params.and_then(|v| v.as_array()).into_iter().flatten() {
+            self.untyped.extend(param.as_str().or_else(|| param.get(0).and_then(|v| v.as_str())));
+        }
+    }
+
+    /// Membres de `class` et de ses parents ; None si l'un d'eux n'est pas connu.
+    fn members(&self, class: &'a str, seen: &mut HashSet<&'a str>) -> Option<HashSet<&'a str>> {
+        if !seen.insert(class) {
+            return None;
+        }
+        let (own, parent) = self.classes.get(class)?.as_ref()?;
+        let mut members = own.clone();
+        if let Some(parent) = parent {
+            members.extend(self.members(parent, seen)?);
+        }
+        Some(members)
+    }
+}
+
+/// Méthodes, champs déclarés et attributs affectés à `this` dans les méthodes.
+fn class_members(arr: &[Value]) -> HashSet<&str> {
+    let mut members = HashSet::new();
+    if let Some(methods) = arr.get(3).and_then(|v| v.as_object()) {
+        members.extend(methods.keys().map(String::as_str));
+        for method in methods.values() {
+            this_attributes(method, &mut members);
+        }
+    }
+    // ["field", nom, ...] ou ["prop", nom, ...]
+    for field in arr.get(5).and_then(|v| v.as_array()).into_iter().flatten() {
+        members.extend(field.get(1).and_then(|v| v.as_str()));
+    }
+    members
+}
+
+fn this_attributes<'a>(node: &'a Value, members: &mut HashSet<&'a str>) {
+    if let Some(arr) = node.as_array() {
+        if arr.first().and_then(|v| v.as_str()) == Some("set_attr")
+            && arr.get(2).and_then(read_name) == Some("this") {
+            members.extend(arr.get(3).and_then(|v| v.as_str()));
+        }
+        for item in arr {
+            this_attributes(item, members);
+        }
+    }
+}
+
+/// Classe de `["new", ["get", "Classe"], args...]`.
+fn instantiated(expr: Option<&Value>) -> Option<&str> {
+    let expr = expr?.as_array()?;
+    (expr.first()?.as_str()? == "new").then_some(())?;
+    expr.get(1).and_then(read_name)
+}
+
+/// Nom lu par `["get", "nom"]`.
+fn read_name(node: &Value) -> Option<&str> {
+    let arr = node.as_array()?;
+    match arr.as_slice() {
+        [Value::String(get), Value::String(name)] if get == "get" => Some(name),
+        _ => None,
+    }
+}
+
+/// Appels `["call_method", line, ["get", x], "nom", args]` (instruction) ou
+/// `["call_method", ["get", x], "nom", args]` (expression).
+fn calls<'a>(
+    node: &'a Value,
+    line: u32,
+    instances: &HashMap<&str, &'a str>,
+    classes: &HashMap<&str, HashSet<&'a str>>,
+    seen: &mut HashMap<(u32, &'a str), usize>,
+    found: &mut Vec<UnknownMember<'a>>,
+) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            let statement_line = arr.get(1).and_then(|v| v.as_u64()).filter(|_| cmd.is_some_and(|c| undefined::STATEMENTS.contains(&c)));
+            let line = statement_line.map_or(line, |l| l.saturating_sub(1) as u32);
+
+            if cmd == Some("call_method") {
+                let rest = if statement_line.is_some() { &arr[2..] } else { &arr[1..] };
+                if let [receiver, Value::String(name), ..] = rest {
+                    let occurrence = seen.entry((line, name.as_str())).or_default();
+                    if let Some(&class) = read_name(receiver).and_then(|receiver| instances.get(receiver))
+                        && let Some(members) = classes.get(class)
+                        && !members.contains(name.as_str()) {
+                        found.push(UnknownMember { line, class, name, occurrence: *occurrence, suggestions: closest(name, members) });
+                    }
+                    *occurrence += 1;
+                }
+            }
+
+            for item in arr {
+                calls(item, line, instances, classes, seen, found);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                calls(item, line, instances, classes, seen, found);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Au plus trois membres proches de `name` (distance d'édition d'au plus la moitié de sa
+/// longueur), du plus proche au plus lointain.
+fn closest<'a>(name: &str, members: &HashSet<&'a str>) -> Vec<&'a str> {
+    let limit = (name.chars().count() / 2).max(1);
+    let mut ranked: Vec<(usize, &str)> = members
+        .iter()
+        .map(|&member| (distance(name, member), member))
+        .filter(|&(d, _)| d <= limit)
+        .collect();
+    ranked.sort_unstable();
+    ranked.into_iter().take(3).map(|(_, member)| member).collect()
+}
+
+/// Distance de Levenshtein, en caractères.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,41 @@
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::line_index::LineIndex;
+use crate::selection;
+
+/// Caractères qui déclenchent la fermeture automatique.
+pub const TRIGGERS: &[&str] = &["{", "(", "["];
+
+/// Fermeture du `opener` que l'utilisateur vient de taper juste avant `position`, si sa
+/// frappe a laissé un ouvrant sans vis-à-vis : rien dans une chaîne ou un commentaire, ni
+/// si le texte le fermait déjà (fermeture insérée par l'éditeur lui-même). Une accolade en
+/// fin de ligne est fermée sur la ligne suivante, à l'indentation de la sienne.
+pub fn closing_edit(text: &str, index: &LineIndex, position: Position, opener: &str) -> Option<TextEdit> {
+    let closer = match opener {
+        "{" => "}",
+        "(" => ")",
+        "[" => "]",
+        _ => return None,
+    };
+    let offset = index.offset(position);
+    // Requête obsolète ou encodage discordant : la position peut tomber au milieu d'un caractère
+    let typed = offset.checked_sub(opener.len()).filter(|&at| text.get(at..).is_some_and(|s| s.starts_with(opener)))?;
+    let rest = text.get(offset..)?;
+
+    let kind = opener.chars().next()?;
+    let count = |text: &str| selection::unclosed(text).into_iter().filter(|&(c, _)| c == kind).count();
+    let before = format!("{}{}", text.get(..typed)?, rest);
+    if count(text) <= count(&before) {
+        return None;
+    }
+
+    let line_start = index.offset(Position { line: position.line, character: 0 });
+    let line = text.get(line_start..)?;
+    let new_text = if opener == "{" && rest.lines().next().unwrap_or("").trim().is_empty() {
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        format!("\n{}}}", indent)
+    } else {
+        closer.to_string()
+    };
+    Some(TextEdit { range: Range { start: position, end: position }, new_text })
+}
@@ -0,0 +1,69 @@
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+
+thread_local! {
+    // Trace d'appels de la dernière panique du thread, relevée par le hook
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+    // Nombre d'appels à `catch` en cours sur le thread
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Panique interceptée par `catch`.
+#[derive(Debug)]
+pub struct Panic {
+    pub message: String,
+    pub backtrace: Option<Backtrace>,
+}
+
+/// Installe un hook qui relève la trace d'appels de chaque panique avant d'appeler le
+/// hook précédent (message sur stderr). Sa résolution en texte n'a lieu qu'à l'affichage.
+/// Une panique attendue par `catch` ne passe pas par le hook précédent, et seul le `catch`
+/// le plus extérieur en reçoit la trace : une panique rattrapée par un `catch` imbriqué
+/// (sonde du loader...) ne coûte pas de relevé.
+pub fn install_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let catching = CATCHING.with(Cell::get);
+        if catching <= 1 {
+            BACKTRACE.with(|backtrace| *backtrace.borrow_mut() = Some(Backtrace::force_capture()));
+        }
+        if catching == 0 {
+            previous(info);
+        }
+    }));
+}
+
+/// Exécute `f` ; une panique devient une erreur avec son message et sa trace d'appels.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, Panic> {
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+    result.map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Panic { message, backtrace: BACKTRACE.with(|backtrace| backtrace.borrow_mut().take()) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catch_returns_message_and_outer_backtrace_only() {
+        install_hook();
+        let outer = catch(|| {
+            let inner = catch(|| panic!("inner"));
+            assert!(inner.as_ref().is_err_and(|p| p.message == "inner" && p.backtrace.is_none()));
+            panic!("outer {}", 1);
+        });
+        let panic = outer.unwrap_err();
+        assert_eq!(panic.message, "outer 1");
+        assert!(panic.backtrace.is_some());
+        assert_eq!(CATCHING.with(Cell::get), 0);
+    }
+}
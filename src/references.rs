@@ -0,0 +1,94 @@
+/// Offsets d'octets de chaque occurrence de l'identifiant `name` dans `text`.
+pub fn identifier_offsets(text: &str, name: &str) -> Vec<usize> {
+    identifiers(text)
+        .into_iter()
+        .filter(|&(_, ident)| ident == name)
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Identifiants de `text` avec leur offset d'octet, dans l'ordre du texte.
+/// Les chaînes et commentaires sont ignorés, à l'exception des interpolations `${...}`
+/// des chaînes multilignes qui sont du code.
+pub fn identifiers(text: &str) -> Vec<(usize, &str)> {
+    let mut found = Vec::new();
+    scan(text, 0, &mut found);
+    found
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn scan<'a>(text: &'a str, base: usize, found: &mut Vec<(usize, &'a str)>) {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); },
+                        '"' | '\n' => break,
+                        _ => {}
+                    }
+                }
+            },
+            '`' => {
+                while let Some((j, c)) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); },
+                        '`' => break,
+                        // Interpolation : analysée comme du code jusqu'à l'accolade fermante
+                        '$' if chars.peek().is_some_and(|&(_, n)| n == '{') => {
+                            chars.next();
+                            let start = j + 2;
+                            let mut depth = 1;
+                            let mut end = text.len();
+                            for (k, c) in chars.by_ref() {
+                                match c {
+                                    '{' => depth += 1,
+                                    '}' => depth -= 1,
+                                    _ => {}
+                                }
+                                if depth == 0 {
+                                    end = k;
+                                    break;
+                                }
+                            }
+                            scan(&text[start..end], base + start, found);
+                        },
+                        _ => {}
+                    }
+                }
+            },
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '/') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            },
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '*') => {
+                chars.next();
+                while let Some((_, c)) = chars.next() {
+                    if c == '*' && chars.peek().is_some_and(|&(_, n)| n == '/') {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+            c if is_ident_char(c) => {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, n)) = chars.peek() {
+                    if !is_ident_char(n) {
+                        break;
+                    }
+                    end = j + n.len_utf8();
+                    chars.next();
+                }
+                found.push((base + i, &text[i..end]));
+            },
+            _ => {}
+        }
+    }
+}
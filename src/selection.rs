@@ -0,0 +1,193 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::{Position, Range, SelectionRange};
+
+use crate::line_index::LineIndex;
+use crate::undefined;
+
+/// Plages d'« expansion de sélection » de chaque position : l'identifiant sous le curseur,
+/// puis les chaînes, parenthèses, crochets et blocs qui l'entourent (contenu, puis
+/// délimiteurs compris), les instructions de l'AST qui le contiennent, enfin tout le
+/// document. Sans AST (document qui ne compile pas), seules les instructions manquent.
+pub fn selection_ranges(ast: Option<&Value>, text: &str, index: &LineIndex, positions: &[Position]) -> Vec<SelectionRange> {
+    let delimiters = delimiters(text);
+    let mut statements = Vec::new();
+    if let Some(ast) = ast {
+        let lines: Vec<usize> = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        collect_statements(ast, text, &lines, &delimiters, &mut statements);
+    }
+
+    positions
+        .iter()
+        .map(|&position| {
+            let offset = index.offset(position);
+            let mut candidates: Vec<(usize, usize)> = Vec::new();
+            candidates.extend(word_at(text, offset));
+            for &(open, close) in delimiters.iter().filter(|&&(open, close)| open <= offset && offset <= close) {
+                candidates.push(trim(text, open + 1, close - 1));
+                candidates.push((open, close));
+            }
+            candidates.extend(statements.iter().copied());
+            candidates.push(trim(text, 0, text.len()));
+
+            // Chaîne de plages emboîtées, de la plus petite qui contient la position à la plus grande
+            candidates.retain(|&(start, end)| start <= offset && offset <= end && start < end);
+            candidates.sort_by_key(|&(start, end)| (end - start, start));
+            let mut chain: Vec<(usize, usize)> = Vec::new();
+            for candidate in candidates {
+                if chain.last().is_none_or(|&(start, end)| candidate.0 <= start && end <= candidate.1 && candidate != (start, end)) {
+                    chain.push(candidate);
+                }
+            }
+
+            let to_range = |(start, end): (usize, usize)| Range { start: index.position(start), end: index.position(end) };
+            chain.into_iter().rev().fold(None, |parent: Option<SelectionRange>, range| {
+                Some(SelectionRange { range: to_range(range), parent: parent.map(Box::new) })
+            })
+            .unwrap_or(SelectionRange { range: Range { start: position, end: position }, parent: None })
+        })
+        .collect()
+}
+
+/// Identifiant qui contient `offset` (ou qui s'achève juste avant).
+fn word_at(text: &str, offset: usize) -> Option<(usize, usize)> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let start = text[..offset].rfind(|c: char| !is_word(c)).map_or(0, |i| i + text[i..].chars().next().map_or(1, char::len_utf8));
+    let end = text[offset..].find(|c: char| !is_word(c)).map_or(text.len(), |i| offset + i);
+    (start < end).then_some((start, end))
+}
+
+/// Chaînes et paires `()`, `[]`, `{}` du texte : (début, fin exclue) en octets, délimiteurs
+/// compris. Les commentaires sont ignorés, comme les délimiteurs sans vis-à-vis.
+pub fn delimiters(text: &str) -> Vec<(usize, usize)> {
+    scan(text).0
+}
+
+/// Parenthèses, crochets et accolades ouvrants sans fermeture, avec leur offset d'octet.
+pub fn unclosed(text: &str) -> Vec<(char, usize)> {
+    scan(text).1
+}
+
+/// Paires fermées (début, fin exclue) et ouvrants restés sans vis-à-vis (caractère, offset).
+type Scan = (Vec<(usize, usize)>, Vec<(char, usize)>);
+
+fn scan(text: &str) -> Scan {
+    let mut found = Vec::new();
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' | '`' => {
+                let mut end = text.len();
+                while let Some((j, n)) = chars.next() {
+                    if n == '\\' && c == '"' {
+                        chars.next();
+                    } else if n == c {
+                        end = j + 1;
+                        break;
+                    }
+                }
+                found.push((i, end));
+            },
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '/') => {
+                for (_, n) in chars.by_ref() {
+                    if n == '\n' {
+                        break;
+                    }
+                }
+            },
+            '/' if chars.peek().is_some_and(|&(_, n)| n == '*') => {
+                chars.next();
+                while let Some((_, n)) = chars.next() {
+                    if n == '*' && chars.peek().is_some_and(|&(_, n)| n == '/') {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+            '(' | '[' | '{' => open.push((c, i)),
+            ')' | ']' | '}' => {
+                let expected = match c {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if let Some(at) = open.iter().rposition(|&(o, _)| o == expected) {
+                    let (_, start) = open[at];
+                    open.truncate(at);
+                    found.push((start, i + 1));
+                }
+            },
+            _ => {}
+        }
+    }
+    (found, open)
+}
+
+/// Plage de chaque instruction de l'AST : de sa ligne jusqu'à l'instruction suivante du
+/// même bloc ou, pour la dernière, jusqu'à l'accolade qui ferme ce bloc. Les blancs et
+/// les lignes de commentaires qui suivent l'instruction n'en font pas partie.
+fn collect_statements(node: &Value, text: &str, lines: &[usize], delimiters: &[(usize, usize)], found: &mut Vec<(usize, usize)>) {
+    let Some(arr) = node.as_array() else {
+        if let Value::Object(map) = node {
+            for item in map.values() {
+                collect_statements(item, text, lines, delimiters, found);
+            }
+        }
+        return;
+    };
+
+    if arr.first().is_some_and(|v| !v.is_string()) {
+        let starts: Vec<Option<u32>> = arr.iter().map(|item| item.as_array().and_then(|a| statement_line(a))).collect();
+        for (i, line) in starts.iter().enumerate() {
+            let Some(&line_start) = line.and_then(|l| lines.get(l as usize)) else {
+                continue;
+            };
+            let start = line_start + (text[line_start..].len() - text[line_start..].trim_start_matches([' ', '\t']).len());
+            // Bloc englobant le plus proche : l'instruction s'arrête avant son accolade fermante
+            let block_end = delimiters
+                .iter()
+                .filter(|&&(open, close)| text[open..].starts_with('{') && open < start && start < close)
+                .map(|&(_, close)| close - 1)
+                .min()
+                .unwrap_or(text.len());
+            let next = starts[i + 1..]
+                .iter()
+                .flatten()
+                .find(|&&next| Some(next) > *line)
+                .and_then(|&next| lines.get(next as usize).copied())
+                .unwrap_or(text.len());
+            let (start, end) = without_trailing_comments(text, start, next.min(block_end));
+            if start < end {
+                found.push((start, end));
+            }
+        }
+    }
+    for item in arr {
+        collect_statements(item, text, lines, delimiters, found);
+    }
+}
+
+fn without_trailing_comments(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let (start, mut end) = trim(text, start, end);
+    while let Some(i) = text[start..end].rfind('\n')
+        && text[start + i + 1..end].trim_start().starts_with("//") {
+        end = trim(text, start, start + i).1;
+    }
+    (start, end)
+}
+
+/// `start..end` sans les blancs des deux bords.
+fn trim(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let slice = &text[start..end.max(start)];
+    let head = slice.len() - slice.trim_start().len();
+    (start + head, start + slice.trim_end().len().max(head))
+}
+
+/// Ligne (base 0) d'une instruction `["cmd", ligne, ...]`.
+fn statement_line(arr: &[Value]) -> Option<u32> {
+    let cmd = arr.first()?.as_str()?;
+    if !undefined::STATEMENTS.contains(&cmd) {
+        return None;
+    }
+    arr.get(1)?.as_u64().map(|line| line.saturating_sub(1) as u32)
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::*;
+
+use crate::completion::KEYWORDS;
+use crate::document::PositionEncoding;
+use crate::line_index::LineIndex;
+use crate::references;
+use crate::symbols::SymbolInfo;
+
+/// Types annoncés au client : la valeur encodée d'un token est son index dans ce tableau.
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::NAMESPACE,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::INTERFACE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::METHOD,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::KEYWORD,
+];
+
+/// Modificateurs annoncés au client : le bit `1 << i` correspond à l'index `i`.
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+];
+
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// Index du type de token d'un genre de symbole dans `TOKEN_TYPES`.
+fn token_type(kind: CompletionItemKind) -> u32 {
+    let token = match kind {
+        CompletionItemKind::MODULE => SemanticTokenType::NAMESPACE,
+        CompletionItemKind::CLASS => SemanticTokenType::CLASS,
+        CompletionItemKind::INTERFACE => SemanticTokenType::INTERFACE,
+        CompletionItemKind::FUNCTION => SemanticTokenType::FUNCTION,
+        CompletionItemKind::METHOD => SemanticTokenType::METHOD,
+        CompletionItemKind::FIELD | CompletionItemKind::PROPERTY => SemanticTokenType::PROPERTY,
+        _ => SemanticTokenType::VARIABLE,
+    };
+    TOKEN_TYPES.iter().position(|t| *t == token).unwrap_or_default() as u32
+}
+
+/// Tokens sémantiques du document, encodés en deltas comme l'attend le protocole.
+/// Les identifiants sont retrouvés dans le texte puis classés d'après les symboles connus ;
+/// ceux qui ne correspondent à aucune déclaration (paramètres, globales natives) sont omis.
+pub fn semantic_tokens(
+    text: &str,
+    index: &LineIndex,
+    encoding: PositionEncoding,
+    symbols: &[SymbolInfo],
+) -> Vec<SemanticToken> {
+    let keyword = TOKEN_TYPES.iter().position(|t| *t == SemanticTokenType::KEYWORD).unwrap_or_default() as u32;
+
+    // Première déclaration de chaque nom, et positions des déclarations
+    let mut kinds: HashMap<&str, CompletionItemKind> = HashMap::new();
+    for symbol in symbols {
+        kinds.entry(symbol.name.as_str()).or_insert(symbol.kind);
+    }
+    let declarations: Vec<Position> = symbols.iter().map(|s| s.range.start).collect();
+
+    let mut tokens = Vec::new();
+    let mut previous = Position::default();
+    for (offset, ident) in references::identifiers(text) {
+        let (token_type, mut modifiers) = if KEYWORDS.contains(&ident) {
+            (keyword, 0)
+        } else if let Some(&kind) = kinds.get(ident) {
+            let readonly = if kind == CompletionItemKind::CONSTANT { READONLY } else { 0 };
+            (token_type(kind), readonly)
+        } else {
+            continue;
+        };
+
+        let start = index.position(offset);
+        if declarations.contains(&start) {
+            modifiers |= DECLARATION;
+        }
+
+        let delta_start = if start.line == previous.line { start.character - previous.character } else { start.character };
+        tokens.push(SemanticToken {
+            delta_line: start.line - previous.line,
+            delta_start,
+            length: encoding.str_len(ident),
+            token_type,
+            token_modifiers_bitset: modifiers,
+        });
+        previous = start;
+    }
+
+    tokens
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::i18n::Locale;
+
+/// Moment où les diagnostics sont recalculés.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidateOn {
+    #[default]
+    Change,
+    Save,
+}
+
+/// Gravité d'un diagnostic choisie par l'utilisateur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Error,
+    Warning,
+    Info,
+    Hint,
+    Off,
+}
+
+/// Gravité des diagnostics de chaque étape (`aegis.diagnostics`) : `{ "loader": "warning" }`.
+/// La gravité d'un code dans `aegis.lints` l'emporte.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct StageLevels {
+    pub lexer: Option<LintLevel>,
+    pub parser: Option<LintLevel>,
+    pub loader: Option<LintLevel>,
+    pub analyzer: Option<LintLevel>,
+}
+
+/// Avertissements facultatifs et gravité de chaque code (`aegis.lints`) :
+/// `{ "shadowing": false, "AEG0300": "error", "AEG0302": "off" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Lints {
+    // Déclaration locale qui masque celle d'une portée englobante
+    pub shadowing: bool,
+    // Gravité imposée, par code de diagnostic
+    #[serde(flatten)]
+    pub levels: HashMap<String, LintLevel>,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Lints { shadowing: true, levels: HashMap::new() }
+    }
+}
+
+/// Réglages du serveur (section `aegis` de la configuration du client).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub validate_on: ValidateOn,
+    // Taille au-delà de laquelle un fichier n'est plus analysé
+    pub max_file_size_kb: u64,
+    // Nombre maximal d'AST de documents ouverts gardés en mémoire
+    pub max_cached_documents: usize,
+    // Délai sans nouvelle frappe avant de revalider un document modifié
+    pub diagnostics_debounce_ms: u64,
+    // Nombre maximal de diagnostics transmis par fichier (`maxNumberOfProblems` accepté aussi)
+    #[serde(alias = "maxNumberOfProblems")]
+    pub max_diagnostics_per_file: usize,
+    // Dossiers où chercher les fichiers importés, après celui du document (relatifs à la racine du workspace)
+    pub import_paths: Vec<PathBuf>,
+    pub lints: Lints,
+    pub diagnostics: StageLevels,
+    // Langue des diagnostics produits par le serveur
+    pub locale: Locale,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            validate_on: ValidateOn::default(),
+            max_file_size_kb: 2048,
+            max_cached_documents: 50,
+            diagnostics_debounce_ms: 200,
+            max_diagnostics_per_file: 100,
+            import_paths: Vec::new(),
+            lints: Lints::default(),
+            diagnostics: StageLevels::default(),
+            locale: Locale::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Limite de taille des fichiers analysés, en octets.
+    pub fn max_file_size(&self) -> u64 {
+        self.max_file_size_kb.saturating_mul(1024)
+    }
+
+    /// Délai de regroupement des modifications avant validation.
+    pub fn diagnostics_debounce(&self) -> Duration {
+        Duration::from_millis(self.diagnostics_debounce_ms)
+    }
+
+    /// Lit les réglages depuis `initializationOptions`, `didChangeConfiguration` ou la
+    /// réponse à `workspace/configuration` (section `aegis`).
+    /// Accepte aussi bien `{ "aegis": { ... } }` que l'objet `{ ... }` directement.
+    /// Un réglage invalide, y compris une entrée de `lints` ou de `diagnostics`, garde sa
+    /// valeur par défaut sans emporter les autres : il est renvoyé avec l'erreur, à
+    /// signaler au client.
+    pub fn from_value(value: &Value) -> (Settings, Vec<String>) {
+        let section = value.get("aegis").unwrap_or(value);
+        let Some(fields) = section.as_object() else {
+            return match serde_json::from_value(section.clone()) {
+                Ok(settings) => (settings, Vec::new()),
+                Err(e) => (Settings::default(), vec![e.to_string()]),
+            };
+        };
+
+        let mut fields = fields.clone();
+        // L'ancien nom s'efface devant le nouveau plutôt que d'entrer en conflit avec lui
+        if fields.contains_key("maxDiagnosticsPerFile") {
+            fields.remove("maxNumberOfProblems");
+        }
+        let mut errors = Vec::new();
+        if let Some(Value::Object(lints)) = fields.get("lints") {
+            let kept = valid_fields::<Lints>(lints, "lints.", &mut errors);
+            fields.insert("lints".to_string(), Value::Object(kept));
+        }
+        if let Some(Value::Object(levels)) = fields.get("diagnostics") {
+            let kept = valid_fields::<StageLevels>(levels, "diagnostics.", &mut errors);
+            fields.insert("diagnostics".to_string(), Value::Object(kept));
+        }
+        let fields = valid_fields::<Settings>(&fields, "", &mut errors);
+        (serde_json::from_value(Value::Object(fields)).unwrap_or_default(), errors)
+    }
+}
+
+/// Champs de `fields` que `T` sait lire un à un (tous ses champs ont une valeur par
+/// défaut) ; les autres sont écartés, avec leur erreur dans `errors`.
+fn valid_fields<T: DeserializeOwned>(fields: &Map<String, Value>, prefix: &str, errors: &mut Vec<String>) -> Map<String, Value> {
+    let mut accepted = Map::new();
+    for (key, field) in fields {
+        let single = Map::from_iter([(key.clone(), field.clone())]);
+        match serde_json::from_value::<T>(Value::Object(single)) {
+            Ok(_) => {
+                accepted.insert(key.clone(), field.clone());
+            },
+            Err(e) => errors.push(format!("'{}{}': {}", prefix, key, e)),
+        }
+    }
+    accepted
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn invalid_lint_level_keeps_other_lints() {
+        let (settings, errors) = Settings::from_value(&json!({
+            "lints": { "shadowing": false, "AEG0300": "loud", "AEG0302": "off" },
+            "diagnostics": { "loader": "warning", "parser": 3 },
+        }));
+        assert!(!settings.lints.shadowing);
+        assert_eq!(settings.lints.levels, HashMap::from([("AEG0302".to_string(), LintLevel::Off)]));
+        assert_eq!(settings.diagnostics.loader, Some(LintLevel::Warning));
+        assert_eq!(settings.diagnostics.parser, None);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("'lints.AEG0300'"));
+        assert!(errors[1].starts_with("'diagnostics.parser'"));
+    }
+
+    #[test]
+    fn invalid_field_keeps_other_fields() {
+        let (settings, errors) = Settings::from_value(&json!({ "aegis": { "maxFileSizeKb": "big", "locale": "fr" } }));
+        assert_eq!(settings.max_file_size_kb, Settings::default().max_file_size_kb);
+        assert_eq!(settings.locale, Locale::Fr);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn canonical_key_wins_over_alias() {
+        let (settings, errors) = Settings::from_value(&json!({ "maxNumberOfProblems": 5, "maxDiagnosticsPerFile": 7 }));
+        assert_eq!(settings.max_diagnostics_per_file, 7);
+        assert!(errors.is_empty());
+
+        let (settings, _) = Settings::from_value(&json!({ "maxNumberOfProblems": 5 }));
+        assert_eq!(settings.max_diagnostics_per_file, 5);
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::undefined;
+
+/// Déclaration locale qui masque un nom d'une portée englobante.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shadowing<'a> {
+    pub name: &'a str,
+    // Lignes (base 0) de la déclaration qui masque et de celle qui est masquée
+    pub line: u32,
+    pub shadowed_line: u32,
+}
+
+/// Portées ouvertes pendant le parcours : une pile de blocs par fonction englobante.
+/// Comme dans la VM, une affectation dans une fonction à un nom qui n'y est pas encore
+/// local déclare une nouvelle locale, et les blocs (`if`, `while`...) libèrent les leurs.
+struct Scopes<'a> {
+    globals: HashMap<&'a str, u32>,
+    functions: Vec<Vec<HashMap<&'a str, u32>>>,
+}
+
+impl<'a> Scopes<'a> {
+    fn is_local(&self, name: &str) -> bool {
+        self.functions.last().is_some_and(|blocks| blocks.iter().any(|block| block.contains_key(name)))
+    }
+
+    /// Ligne de la déclaration de `name` visible hors de la fonction courante.
+    fn outer(&self, name: &str) -> Option<u32> {
+        let enclosing = self.functions.len().saturating_sub(1);
+        self.functions[..enclosing]
+            .iter()
+            .rev()
+            .flat_map(|blocks| blocks.iter().rev())
+            .find_map(|block| block.get(name).copied())
+            .or_else(|| self.globals.get(name).copied())
+    }
+
+    /// Déclare `name` dans le bloc courant, en relevant le nom qu'elle masque. `fresh` :
+    /// la déclaration crée toujours une nouvelle locale (paramètre, itérateur...), même si
+    /// le nom est déjà local à la fonction.
+    fn declare(&mut self, name: &'a str, line: u32, fresh: bool, found: &mut Vec<Shadowing<'a>>) {
+        if self.functions.is_empty() || (!fresh && self.is_local(name)) {
+            return;
+        }
+        let shadowed = if fresh && self.is_local(name) {
+            self.functions.last().and_then(|blocks| blocks.iter().rev().find_map(|block| block.get(name).copied()))
+        } else {
+            self.outer(name)
+        };
+        if let Some(shadowed_line) = shadowed {
+            found.push(Shadowing { name, line, shadowed_line });
+        }
+        if let Some(block) = self.functions.last_mut().and_then(|blocks| blocks.last_mut()) {
+            block.insert(name, line);
+        }
+    }
+
+    fn open_block(&mut self) {
+        if let Some(blocks) = self.functions.last_mut() {
+            blocks.push(HashMap::new());
+        }
+    }
+
+    fn close_block(&mut self) {
+        if let Some(blocks) = self.functions.last_mut() {
+            blocks.pop();
+        }
+    }
+}
+
+/// Variables, paramètres, itérateurs et variables d'erreur déclarés dans une fonction,
+/// une méthode ou un namespace alors que leur nom désigne déjà une déclaration globale
+/// ou une locale d'une fonction englobante. Deux blocs voisins qui déclarent le même nom
+/// ne se masquent pas.
+pub fn shadowed_declarations(ast: &Value) -> Vec<Shadowing<'_>> {
+    let mut globals = HashMap::new();
+    collect_globals(ast, &mut globals);
+    let mut scopes = Scopes { globals, functions: Vec::new() };
+    let mut found = Vec::new();
+    visit(ast, 0, &mut scopes, &mut found);
+    found
+}
+
+/// Déclarations du niveau global, blocs de premier niveau compris (première occurrence).
+fn collect_globals<'a>(node: &'a Value, globals: &mut HashMap<&'a str, u32>) {
+    let Some(arr) = node.as_array() else {
+        return;
+    };
+    let cmd = arr.first().and_then(|v| v.as_str());
+    let line = statement_line(arr);
+    match (cmd, arr.get(2).and_then(|v| v.as_str()), line) {
+        (Some("set" | "const" | "function" | "class" | "interface" | "namespace" | "enum"), Some(name), Some(line)) => {
+            globals.entry(name).or_insert(line);
+        },
+        (Some("foreach"), Some(name), Some(line)) => {
+            globals.entry(name).or_insert(line);
+        },
+        _ => {}
+    }
+    // Les corps de fonctions, classes et namespaces ne déclarent rien de global
+    if matches!(cmd, Some("function" | "lambda" | "class" | "namespace")) {
+        return;
+    }
+    if cmd == Some("try")
+        && let (Some(name), Some(line)) = (arr.get(3).and_then(|v| v.as_str()), line) {
+        globals.entry(name).or_insert(line);
+    }
+    for item in arr {
+        collect_globals(item, globals);
+    }
+}
+
+fn visit<'a>(node: &'a Value, line: u32, scopes: &mut Scopes<'a>, found: &mut Vec<Shadowing<'a>>) {
+    let Some(arr) = node.as_array() else {
+        if let Value::Object(map) = node {
+            for item in map.values() {
+                visit(item, line, scopes, found);
+            }
+        }
+        return;
+    };
+    let Some(cmd) = arr.first().and_then(|v| v.as_str()) else {
+        // Liste d'instructions : un bloc
+        scopes.open_block();
+        for item in arr {
+            visit(item, line, scopes, found);
+        }
+        scopes.close_block();
+        return;
+    };
+    let line = statement_line(arr).unwrap_or(line);
+    let name = arr.get(2).and_then(|v| v.as_str());
+
+    match cmd {
+        // ["function", line, "nom", params, ret, body]
+        "function" => {
+            if let Some(name) = name {
+                scopes.declare(name, line, false, found);
+            }
+            function(arr.get(3), arr.get(5), line, scopes, found);
+        },
+        // ["lambda", params, body]
+        "lambda" => function(arr.get(1), arr.get(2), line, scopes, found),
+        // ["class", line, "nom", {méthode: [params, body, ...]}, ...]
+        "class" => {
+            for method in arr.get(3).and_then(|v| v.as_object()).into_iter().flat_map(|m| m.values()) {
+                function(method.get(0), method.get(1), line, scopes, found);
+            }
+        },
+        // ["namespace", line, "nom", body]
+        "namespace" => function(None, arr.get(3), line, scopes, found),
+        // ["set", line, "nom", type, expr]
+        "set" => {
+            for item in &arr[3..] {
+                visit(item, line, scopes, found);
+            }
+            if let Some(name) = name {
+                scopes.declare(name, line, false, found);
+            }
+        },
+        // ["foreach", line, "nom", iterable, body]
+        "foreach" => {
+            if let Some(iterable) = arr.get(3) {
+                visit(iterable, line, scopes, found);
+            }
+            scopes.open_block();
+            if let Some(name) = name {
+                scopes.declare(name, line, true, found);
+            }
+            if let Some(body) = arr.get(4) {
+                visit(body, line, scopes, found);
+            }
+            scopes.close_block();
+        },
+        // ["try", line, body, err_var, catch_body]
+        "try" => {
+            if let Some(body) = arr.get(2) {
+                visit(body, line, scopes, found);
+            }
+            scopes.open_block();
+            if let Some(error) = arr.get(3).and_then(|v| v.as_str()) {
+                scopes.declare(error, line, true, found);
+            }
+            if let Some(body) = arr.get(4) {
+                visit(body, line, scopes, found);
+            }
+            scopes.close_block();
+        },
+        _ => {
+            for item in &arr[1..] {
+                visit(item, line, scopes, found);
+            }
+        },
+    }
+}
+
+/// Nouvelle fonction : ses paramètres (`[["a", null], ...]`, ou `["a", ...]` pour une
+/// lambda), puis son corps.
+fn function<'a>(params: Option<&'a Value>, body: Option<&'a Value>, line: u32, scopes: &mut Scopes<'a>, found: &mut Vec<Shadowing<'a>>) {
+    scopes.functions.push(vec![HashMap::new()]);
+    for param in params.and_then(|v| v.as_array()).into_iter().flatten() {
+        if let Some(name) = param.as_str().or_else(|| param.get(0).and_then(|v| v.as_str())) {
+            scopes.declare(name, line, true, found);
+        }
+    }
+    if let Some(body) = body {
+        visit(body, line, scopes, found);
+    }
+    scopes.functions.pop();
+}
+
+/// Ligne (base 0) d'une instruction `["cmd", ligne, ...]`.
+fn statement_line(arr: &[Value]) -> Option<u32> {
+    let cmd = arr.first()?.as_str()?;
+    if !undefined::STATEMENTS.contains(&cmd) {
+        return None;
+    }
+    arr.get(1)?.as_u64().map(|line| line.saturating_sub(1) as u32)
+}
@@ -0,0 +1,713 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+use tower_lsp::lsp_types::*;
+
+use crate::document::PositionEncoding;
+
+/// Symbole déclaré dans un document, avec sa position de déclaration.
+#[derive(Debug, Clone)]
+pub struct SymbolInfo {
+    pub name: String,
+    pub kind: CompletionItemKind,
+    pub detail: String,
+    // Plage du nom à sa déclaration
+    pub range: Range,
+    // Étendue complète de la déclaration (corps compris pour les blocs)
+    pub full_range: Range,
+    // Déclarations imbriquées (corps des fonctions, namespaces)
+    pub children: Vec<SymbolInfo>,
+    // Étendue du bloc englobant (None au niveau global)
+    pub scope: Option<Range>,
+    // Nom de la déclaration englobante (classe d'une méthode, fonction d'une locale)
+    pub container: Option<String>,
+    // Classe de la valeur, déduite de l'annotation ou du `new` qui l'a créée
+    pub type_name: Option<String>,
+    // Paramètres des fonctions et méthodes, avec leur type éventuel ("x: int")
+    pub params: Vec<String>,
+    // Chemin du namespace qui déclare directement le symbole ("Outer.Inner")
+    pub namespace: Option<String>,
+}
+
+impl SymbolInfo {
+    /// Entrée de complétion. Avec `snippets` (si le client les gère), une fonction ou une
+    /// méthode s'insère avec ses parenthèses, le curseur entre les deux.
+    pub fn completion_item(&self, snippets: bool) -> CompletionItem {
+        let mut item = CompletionItem {
+            label: self.name.clone(),
+            kind: Some(self.kind),
+            detail: Some(self.detail.clone()),
+            ..Default::default()
+        };
+
+        if snippets && matches!(self.kind, CompletionItemKind::FUNCTION | CompletionItemKind::METHOD) {
+            item.insert_text = Some(format!("{}($0)", self.name));
+            item.insert_text_format = Some(InsertTextFormat::SNIPPET);
+        }
+
+        item
+    }
+
+    /// Indique si le symbole est accessible depuis `position`.
+    /// Fonctions, classes, interfaces et namespaces restent visibles partout, sauf
+    /// déclarés dans un namespace : seulement à l'intérieur, comme les variables locales
+    /// dans leur bloc. Les membres de classe ne sont proposés qu'après `objet.`.
+    pub fn visible_at(&self, position: Position) -> bool {
+        if self.is_member() {
+            return false;
+        }
+        let global = matches!(
+            self.kind,
+            CompletionItemKind::FUNCTION
+                | CompletionItemKind::CLASS
+                | CompletionItemKind::INTERFACE
+                | CompletionItemKind::MODULE
+        );
+        (global && self.namespace.is_none()) || self.scope.is_none_or(|scope| contains(scope, position))
+    }
+
+    /// Vrai si `position` est dans l'étendue de la déclaration.
+    pub fn encloses(&self, position: Position) -> bool {
+        contains(self.full_range, position)
+    }
+
+    pub fn is_member(&self) -> bool {
+        matches!(
+            self.kind,
+            CompletionItemKind::METHOD | CompletionItemKind::FIELD | CompletionItemKind::PROPERTY
+        )
+    }
+
+    /// Signature affichée par l'aide à la saisie : `nom(a, b: int)`.
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.params.join(", "))
+    }
+
+    fn symbol_kind(&self) -> SymbolKind {
+        match self.kind {
+            CompletionItemKind::FUNCTION => SymbolKind::FUNCTION,
+            CompletionItemKind::CLASS => SymbolKind::CLASS,
+            CompletionItemKind::INTERFACE => SymbolKind::INTERFACE,
+            CompletionItemKind::MODULE => SymbolKind::NAMESPACE,
+            CompletionItemKind::CONSTANT => SymbolKind::CONSTANT,
+            CompletionItemKind::METHOD => SymbolKind::METHOD,
+            CompletionItemKind::FIELD => SymbolKind::FIELD,
+            CompletionItemKind::PROPERTY => SymbolKind::PROPERTY,
+            _ => SymbolKind::VARIABLE,
+        }
+    }
+
+    #[allow(deprecated)]
+    pub fn document_symbol(&self) -> DocumentSymbol {
+        DocumentSymbol {
+            name: self.name.clone(),
+            detail: Some(self.detail.clone()),
+            kind: self.symbol_kind(),
+            tags: None,
+            deprecated: None,
+            range: self.full_range,
+            selection_range: self.range,
+            children: if self.children.is_empty() {
+                None
+            } else {
+                Some(self.children.iter().map(SymbolInfo::document_symbol).collect())
+            },
+        }
+    }
+
+    /// Vrai si le nom contient `query`, sans tenir compte de la casse (requête vide : tout symbole).
+    pub fn matches(&self, query: &str) -> bool {
+        self.name.to_lowercase().contains(&query.to_lowercase())
+    }
+
+    /// Entrée de la recherche de symboles du workspace, déclarée dans le document `uri`.
+    #[allow(deprecated)]
+    pub fn symbol_information(&self, uri: &Url) -> SymbolInformation {
+        SymbolInformation {
+            name: self.name.clone(),
+            kind: self.symbol_kind(),
+            tags: None,
+            deprecated: None,
+            location: Location { uri: uri.clone(), range: self.range },
+            container_name: self.container.clone(),
+        }
+    }
+
+    /// Élément de la hiérarchie d'appels, déclaré dans le document `uri`.
+    pub fn call_hierarchy_item(&self, uri: &Url) -> CallHierarchyItem {
+        CallHierarchyItem {
+            name: self.name.clone(),
+            kind: self.symbol_kind(),
+            tags: None,
+            detail: Some(self.signature()),
+            uri: uri.clone(),
+            range: self.full_range,
+            selection_range: self.range,
+            data: None,
+        }
+    }
+}
+
+/// Parcourt l'AST JSON et collecte les déclarations sous forme d'arbre.
+/// `text` sert à retrouver la colonne des noms sur leur ligne de déclaration.
+pub fn symbol_tree(ast: &Value, text: &str, encoding: PositionEncoding) -> Vec<SymbolInfo> {
+    let src = Source { lines: text.lines().collect(), encoding };
+    let mut symbols = Vec::new();
+    walk(ast, &src, &mut symbols);
+    symbols
+}
+
+/// Liste à plat de toutes les déclarations (parent avant ses enfants).
+pub fn extract_symbols(ast: &Value, text: &str, encoding: PositionEncoding) -> Vec<SymbolInfo> {
+    fn flatten(symbols: Vec<SymbolInfo>, out: &mut Vec<SymbolInfo>) {
+        for mut symbol in symbols {
+            let children = std::mem::take(&mut symbol.children);
+            out.push(symbol);
+            flatten(children, out);
+        }
+    }
+
+    let mut flat = Vec::new();
+    flatten(symbol_tree(ast, text, encoding), &mut flat);
+    flat
+}
+
+/// Déclarations faites directement dans le namespace `path` ("Outer.Inner").
+pub fn namespace_members<'a>(symbols: &'a [SymbolInfo], path: &str) -> Vec<&'a SymbolInfo> {
+    symbols.iter().filter(|s| s.namespace.as_deref() == Some(path)).collect()
+}
+
+/// Membres (méthodes, champs, propriétés) de la classe `class`.
+pub fn class_members<'a>(symbols: &'a [SymbolInfo], class: &str) -> Vec<&'a SymbolInfo> {
+    symbols
+        .iter()
+        .filter(|s| s.is_member() && s.container.as_deref() == Some(class))
+        .collect()
+}
+
+/// Lignes du document source et encodage des colonnes à produire.
+struct Source<'a> {
+    lines: Vec<&'a str>,
+    encoding: PositionEncoding,
+}
+
+/// Élimine les doublons (même nom et même genre), en gardant la première occurrence :
+/// une variable réaffectée ou redéclarée dans un bloc imbriqué n'apparaît qu'une fois.
+pub fn dedup(symbols: &[SymbolInfo]) -> Vec<&SymbolInfo> {
+    let mut seen: HashMap<&str, Vec<CompletionItemKind>> = HashMap::new();
+    symbols
+        .iter()
+        .filter(|s| {
+            let kinds = seen.entry(s.name.as_str()).or_default();
+            if kinds.contains(&s.kind) {
+                false
+            } else {
+                kinds.push(s.kind);
+                true
+            }
+        })
+        .collect()
+}
+
+/// Déclarations qui redéfinissent un nom déjà déclaré dans la même portée, chacune avec
+/// la première déclaration. Les variables sont ignorées : dans l'AST, `var x = 1` et une
+/// simple réaffectation `x = 2` ne se distinguent pas.
+pub fn duplicates(symbols: &[SymbolInfo]) -> Vec<(&SymbolInfo, &SymbolInfo)> {
+    const DECLARATIONS: &[CompletionItemKind] = &[
+        CompletionItemKind::FUNCTION,
+        CompletionItemKind::CLASS,
+        CompletionItemKind::INTERFACE,
+        CompletionItemKind::CONSTANT,
+    ];
+
+    let mut seen: HashMap<&str, Vec<&SymbolInfo>> = HashMap::new();
+    let mut found = Vec::new();
+    for symbol in symbols.iter().filter(|s| DECLARATIONS.contains(&s.kind)) {
+        let previous = seen.entry(symbol.name.as_str()).or_default();
+        match previous.iter().find(|p| p.scope == symbol.scope && p.container == symbol.container) {
+            Some(first) => found.push((symbol, *first)),
+            None => previous.push(symbol),
+        }
+    }
+    found
+}
+
+/// Noms lus quelque part dans l'AST (`["get", nom]`). La lecture d'une variable dans sa
+/// propre réaffectation (`x += 1`, `x++`, `x = x * 2`) ne compte pas : elle ne sert qu'à l'écrire.
+pub fn reads(ast: &Value) -> HashSet<&str> {
+    fn collect<'a>(value: &'a Value, assigned: Option<&str>, reads: &mut HashSet<&'a str>) {
+        match value {
+            Value::Array(arr) => {
+                let cmd = arr.first().and_then(|v| v.as_str());
+                if cmd == Some("get") && arr.len() == 2
+                    && let Some(name) = arr[1].as_str() {
+                    if assigned != Some(name) {
+                        reads.insert(name);
+                    }
+                    return;
+                }
+                // ["set", line, "nom", type, expr]
+                if cmd == Some("set")
+                    && let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
+                    for item in &arr[3..] {
+                        collect(item, Some(name), reads);
+                    }
+                    return;
+                }
+                for item in arr {
+                    collect(item, assigned, reads);
+                }
+            },
+            Value::Object(map) => {
+                for item in map.values() {
+                    collect(item, assigned, reads);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    let mut found = HashSet::new();
+    collect(ast, None, &mut found);
+    found
+}
+
+/// Variables (`var` ou affectation) dont la valeur n'est jamais lue, chacune à sa première
+/// déclaration dans sa portée. Les paramètres, itérateurs et variables d'erreur ne sont pas
+/// concernés, ni les variables d'un namespace, lues sous la forme `Ns.nom`.
+pub fn unused_variables<'a>(symbols: &'a [SymbolInfo], reads: &HashSet<&str>) -> Vec<&'a SymbolInfo> {
+    let mut unused: Vec<&SymbolInfo> = Vec::new();
+    for symbol in symbols {
+        let candidate = symbol.kind == CompletionItemKind::VARIABLE
+            && symbol.detail == "Variable"
+            && symbol.namespace.is_none()
+            && !reads.contains(symbol.name.as_str());
+        let reported = unused
+            .iter()
+            .any(|u| u.name == symbol.name && u.scope == symbol.scope && u.container == symbol.container);
+        if candidate && !reported {
+            unused.push(symbol);
+        }
+    }
+    unused
+}
+
+fn walk(ast: &Value, src: &Source, symbols: &mut Vec<SymbolInfo>) {
+    if let Some(arr) = ast.as_array() {
+        if !arr.is_empty() && arr[0].is_string() {
+            analyze_instruction(arr, src, symbols);
+        } else {
+            for item in arr {
+                walk(item, src, symbols);
+            }
+        }
+    }
+}
+
+fn new_symbol(
+    src: &Source,
+    arr: &[Value],
+    name: &str,
+    kind: CompletionItemKind,
+    detail: &str,
+) -> SymbolInfo {
+    let line = arr.get(1).and_then(|v| v.as_u64()).unwrap_or(1);
+    symbol_at(src, line.saturating_sub(1) as usize, name, kind, detail)
+}
+
+/// Symbole dont le nom est recherché à partir de la ligne `line` (base 0).
+fn symbol_at(src: &Source, line: usize, name: &str, kind: CompletionItemKind, detail: &str) -> SymbolInfo {
+    let range = find_name(src, line, name);
+    SymbolInfo {
+        name: name.to_string(),
+        kind,
+        detail: detail.to_string(),
+        range,
+        full_range: range,
+        children: Vec::new(),
+        scope: None,
+        container: None,
+        type_name: None,
+        params: Vec::new(),
+        namespace: None,
+    }
+}
+
+fn push_symbol(
+    symbols: &mut Vec<SymbolInfo>,
+    src: &Source,
+    arr: &[Value],
+    name: &str,
+    kind: CompletionItemKind,
+    detail: &str,
+) {
+    symbols.push(new_symbol(src, arr, name, kind, detail));
+}
+
+/// Déclaration de bloc (fonction, classe, namespace) : son étendue va de la
+/// ligne de déclaration jusqu'à l'accolade fermante, et `body` fournit ses enfants.
+fn push_block_symbol(
+    symbols: &mut Vec<SymbolInfo>,
+    src: &Source,
+    arr: &[Value],
+    name: &str,
+    kind: CompletionItemKind,
+    detail: &str,
+    body: Option<&Value>,
+) {
+    let symbol = new_symbol(src, arr, name, kind, detail);
+    symbols.push(with_block(src, symbol, body));
+}
+
+/// Complète `symbol` avec l'étendue de son bloc et les déclarations de `body`.
+fn with_block(src: &Source, mut symbol: SymbolInfo, body: Option<&Value>) -> SymbolInfo {
+    let start = symbol.range.start.line as usize;
+    let line_text = src.lines.get(start).copied().unwrap_or("");
+    let indent = line_text.len() - line_text.trim_start().len();
+    symbol.full_range = Range {
+        start: Position { line: start as u32, character: src.encoding.column(line_text, indent) },
+        end: block_end(&src.lines, start, src.encoding),
+    };
+
+    if let Some(body) = body {
+        walk(body, src, &mut symbol.children);
+    }
+    adopt(&mut symbol);
+    symbol
+}
+
+/// Rattache les enfants directs de `parent` à son bloc.
+fn adopt(parent: &mut SymbolInfo) {
+    for child in &mut parent.children {
+        child.scope = Some(parent.full_range);
+        child.container = Some(parent.name.clone());
+    }
+}
+
+/// Associe les déclarations du namespace `namespace`, de chemin `path`, et celles de ses
+/// namespaces imbriqués à leur chemin complet. Un namespace englobant repasse ensuite
+/// sur ses descendants avec le chemin allongé.
+fn qualify(namespace: &mut SymbolInfo, path: &str) {
+    for child in &mut namespace.children {
+        child.namespace = Some(path.to_string());
+        if child.kind == CompletionItemKind::MODULE {
+            let nested = format!("{}.{}", path, child.name);
+            qualify(child, &nested);
+        }
+    }
+}
+
+/// Méthodes, champs et propriétés d'un nœud `class`, cherchés à partir de la ligne `line`.
+/// ["class", line, name, {méthode: [params, body, static, final]}, parent, [champs], ...]
+fn class_members_of(arr: &[Value], src: &Source, line: usize) -> Vec<SymbolInfo> {
+    let mut members = Vec::new();
+
+    if let Some(methods) = arr.get(3).and_then(|v| v.as_object()) {
+        for (name, def) in methods {
+            let is_static = def.get(2).and_then(|v| v.as_bool()).unwrap_or(false);
+            let detail = if is_static { "Static Method" } else { "Method" };
+            let mut symbol = symbol_at(src, line, name, CompletionItemKind::METHOD, detail);
+            symbol.range = find_method(src, line, name).unwrap_or(symbol.range);
+            symbol.full_range = symbol.range;
+            symbol.params = param_labels(def.get(0));
+            members.push(with_block(src, symbol, def.get(1)));
+        }
+    }
+
+    // ["field", name, vis, default, static, type] ou ["prop", name, vis, static, getter, setter]
+    if let Some(fields) = arr.get(5).and_then(|v| v.as_array()) {
+        for field in fields {
+            let (Some(tag), Some(name)) = (field.get(0).and_then(|v| v.as_str()), field.get(1).and_then(|v| v.as_str())) else {
+                continue;
+            };
+            let (kind, detail) = match tag {
+                "prop" => (CompletionItemKind::PROPERTY, "Property"),
+                _ => (CompletionItemKind::FIELD, "Field"),
+            };
+            members.push(symbol_at(src, line, name, kind, detail));
+        }
+    }
+
+    members
+}
+
+fn analyze_instruction(arr: &[Value], src: &Source, symbols: &mut Vec<SymbolInfo>) {
+    if arr.is_empty() { return; }
+
+    let cmd = arr[0].as_str().unwrap_or("");
+
+    match cmd {
+        // --- VARIABLES & CONSTANTES ---
+        "var" | "set" => {
+            // ["var", line, "nom", type, expr]
+            if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
+                let mut symbol = new_symbol(src, arr, name, CompletionItemKind::VARIABLE, "Variable");
+                symbol.type_name = value_type(arr.get(3), arr.get(4));
+                symbols.push(symbol);
+            }
+        },
+        "const" => {
+            // ["const", line, "nom", expr]
+            if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
+                push_symbol(symbols, src, arr, name, CompletionItemKind::CONSTANT, "Constant");
+            }
+        },
+
+        // --- FONCTIONS ---
+        "function" | "func" => {
+            // ["function", line, "nom", params, ret, body]
+            // Récursion dans le corps (index 5 habituellement)
+            if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
+                let mut symbol = new_symbol(src, arr, name, CompletionItemKind::FUNCTION, "Function");
+                symbol.params = param_labels(arr.get(3));
+                symbols.push(with_block(src, symbol, arr.get(5)));
+            } else if let Some(body) = arr.get(5) {
+                walk(body, src, symbols);
+            }
+        },
+
+        // --- CLASSES (Nouvelle syntaxe avec objet de définition) ---
+        "class" => {
+            // Format possible 1 : ["class", line, "Name", ...] (Vieux)
+            // Format possible 2 : ["class", line, { "name": "Name", ... }] (Nouveau struct)
+
+            let class_name = if let Some(n) = arr.get(2).and_then(|v| v.as_str()) {
+                Some(n)
+            } else if let Some(obj) = arr.get(2).and_then(|v| v.as_object()) {
+                obj.get("name").and_then(|v| v.as_str())
+            } else {
+                None
+            };
+
+            if let Some(name) = class_name {
+                let mut class = with_block(src, new_symbol(src, arr, name, CompletionItemKind::CLASS, "Class"), None);
+                class.children = class_members_of(arr, src, class.range.start.line as usize);
+                adopt(&mut class);
+                symbols.push(class);
+            }
+        },
+
+        // --- INTERFACES ---
+        "interface" => {
+            // ["interface", line, { "name": "IParams", ... }] ou ["interface", line, "Name", ...]
+            let iface_name = if let Some(n) = arr.get(2).and_then(|v| v.as_str()) {
+                Some(n)
+            } else if let Some(obj) = arr.get(2).and_then(|v| v.as_object()) {
+                obj.get("name").and_then(|v| v.as_str())
+            } else {
+                None
+            };
+
+            if let Some(name) = iface_name {
+                push_symbol(symbols, src, arr, name, CompletionItemKind::INTERFACE, "Interface");
+            }
+        },
+
+        // --- NAMESPACES ---
+        "namespace" => {
+            if let Some(name) = arr.get(2).and_then(|v| v.as_str()) {
+                push_block_symbol(symbols, src, arr, name, CompletionItemKind::MODULE, "Namespace", arr.get(3));
+                if let Some(namespace) = symbols.last_mut() {
+                    qualify(namespace, name);
+                }
+            } else if let Some(body) = arr.get(3) {
+                walk(body, src, symbols);
+            }
+        },
+
+        // --- FOREACH (Nouveau) ---
+        "foreach" => {
+            // ["foreach", line, "var_iter", iterable, body]
+            if let Some(var_name) = arr.get(2).and_then(|v| v.as_str()) {
+                push_symbol(symbols, src, arr, var_name, CompletionItemKind::VARIABLE, "Iterator");
+            }
+            // Récursion dans le corps (index 4)
+            if let Some(body) = arr.get(4) {
+                walk(body, src, symbols);
+            }
+        },
+
+        // --- BLOCS DE RÉCURSION ---
+        "if" => {
+            // ["if", line, cond, then, else]
+            if let Some(then_block) = arr.get(3) {
+                walk(then_block, src, symbols);
+            }
+            if let Some(else_block) = arr.get(4) {
+                walk(else_block, src, symbols);
+            }
+        },
+        "while" => {
+            // ["while", line, cond, body]
+            if let Some(body) = arr.get(3) {
+                walk(body, src, symbols);
+            }
+        },
+        "try_catch" | "try" => {
+            // ["try_catch", line, try_body, err_var, catch_body]
+            if let Some(try_body) = arr.get(2) {
+                walk(try_body, src, symbols);
+            }
+            // Ajout de la variable d'erreur au scope
+            if let Some(err_var) = arr.get(3).and_then(|v| v.as_str()) {
+                push_symbol(symbols, src, arr, err_var, CompletionItemKind::VARIABLE, "Error Variable");
+            }
+            if let Some(catch_body) = arr.get(4) {
+                walk(catch_body, src, symbols);
+            }
+        },
+
+        _ => {}
+    }
+}
+
+/// Libellés des paramètres `[[nom, type], ...]` d'une fonction ou méthode.
+fn param_labels(params: Option<&Value>) -> Vec<String> {
+    let Some(params) = params.and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+
+    params
+        .iter()
+        .filter_map(|param| {
+            let name = param.get(0).and_then(|v| v.as_str())?;
+            Some(match param.get(1).and_then(|v| v.as_str()) {
+                Some(ty) => format!("{}: {}", name, ty),
+                None => name.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Classe d'une variable : son annotation de type, sinon la classe instanciée par `new`.
+pub fn value_type(annotation: Option<&Value>, expr: Option<&Value>) -> Option<String> {
+    if let Some(name) = annotation.and_then(|v| v.as_str()) {
+        return Some(name.to_string());
+    }
+
+    // ["new", ["get", "Classe"], args...] ou ["new", ["get_attr", ns, "Classe"], args...]
+    let expr = expr?.as_array()?;
+    if expr.first()?.as_str()? != "new" {
+        return None;
+    }
+    let callee = expr.get(1)?.as_array()?;
+    callee.last()?.as_str().map(str::to_string)
+}
+
+/// Plage du nom `name` à partir de la ligne `line` (base 0).
+/// La ligne de déclaration ne contient pas toujours le nom (ex: variable du `catch`),
+/// on cherche donc sur les lignes suivantes avant de se rabattre sur le début de la ligne.
+fn find_name(src: &Source, line: usize, name: &str) -> Range {
+    for (l, text) in src.lines.iter().enumerate().skip(line).take(50) {
+        if let Some(col) = find_word(text, name) {
+            let start = src.encoding.column(text, col);
+            let end = start + src.encoding.str_len(name);
+            return Range {
+                start: Position { line: l as u32, character: start },
+                end: Position { line: l as u32, character: end },
+            };
+        }
+    }
+
+    Range {
+        start: Position { line: line as u32, character: 0 },
+        end: Position { line: line as u32, character: src.encoding.str_len(name) },
+    }
+}
+
+/// Position juste après l'accolade fermant le premier bloc ouvert à partir de `line`.
+/// Les chaînes et commentaires sont ignorés ; sans fermeture, on s'arrête en fin de document.
+pub fn block_end(lines: &[&str], line: usize, encoding: PositionEncoding) -> Position {
+    let mut depth = 0;
+    let mut opened = false;
+    let mut in_block_comment = false;
+    let mut in_backtick = false;
+
+    for (l, text) in lines.iter().enumerate().skip(line) {
+        let mut in_string = false;
+        let mut chars = text.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if in_block_comment {
+                if c == '*' && chars.peek().is_some_and(|&(_, n)| n == '/') {
+                    chars.next();
+                    in_block_comment = false;
+                }
+                continue;
+            }
+            if in_backtick {
+                in_backtick = c != '`';
+                continue;
+            }
+            if in_string {
+                match c {
+                    '\\' => { chars.next(); },
+                    '"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match c {
+                '"' => in_string = true,
+                '`' => in_backtick = true,
+                '/' if chars.peek().is_some_and(|&(_, n)| n == '/') => break,
+                '/' if chars.peek().is_some_and(|&(_, n)| n == '*') => {
+                    chars.next();
+                    in_block_comment = true;
+                },
+                '{' => {
+                    depth += 1;
+                    opened = true;
+                },
+                '}' if opened => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Position { line: l as u32, character: encoding.column(text, i + 1) };
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    let last = lines.len().saturating_sub(1);
+    let last_text = lines.get(last).unwrap_or(&"");
+    Position { line: last as u32, character: encoding.str_len(last_text) }
+}
+
+fn contains(range: Range, position: Position) -> bool {
+    let key = |p: Position| (p.line, p.character);
+    key(range.start) <= key(position) && key(position) <= key(range.end)
+}
+
+/// Plage du nom de la méthode `name` à sa déclaration, à partir de la ligne `line` : en
+/// début de ligne, après d'éventuels modificateurs, et suivi de ses paramètres. Un appel
+/// `this.name()` d'une méthode précédente n'est pas pris pour la déclaration.
+fn find_method(src: &Source, line: usize, name: &str) -> Option<Range> {
+    const MODIFIERS: &[&str] = &["public", "private", "protected", "static", "final"];
+    for (l, text) in src.lines.iter().enumerate().skip(line) {
+        let Some(col) = find_word(text, name) else {
+            continue;
+        };
+        let declares = text[..col].split_whitespace().all(|word| MODIFIERS.contains(&word))
+            && text[col + name.len()..].trim_start().starts_with('(');
+        if declares {
+            let start = src.encoding.column(text, col);
+            return Some(Range {
+                start: Position { line: l as u32, character: start },
+                end: Position { line: l as u32, character: start + src.encoding.str_len(name) },
+            });
+        }
+    }
+    None
+}
+
+/// Offset d'octet de la première occurrence de `word` comme mot entier dans `text`.
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).map(|(i, _)| i).find(|&i| {
+        let before = text[..i].chars().next_back().is_none_or(|c| !is_ident(c));
+        let after = text[i + word.len()..].chars().next().is_none_or(|c| !is_ident(c));
+        before && after
+    })
+}
@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::builtins;
+
+/// Nœuds qui portent leur ligne en deuxième position (["print", line, ...]) ; les
+/// expressions comme ["+", 1, 2] n'en ont pas.
+pub const STATEMENTS: &[&str] = &[
+    "set", "const", "function", "class", "interface", "namespace", "enum", "import",
+    "if", "while", "foreach", "try", "switch", "return", "break", "continue", "throw",
+    "print", "input", "call", "call_method", "set_attr", "super_call",
+];
+
+/// Noms lus (`["get", nom]`) sans être déclarés nulle part : ni dans le document, ni parmi
+/// `external` (noms apportés par les imports), ni parmi les natives. Chacun est donné avec
+/// la ligne (base 0) de l'instruction qui le lit, une fois par ligne.
+/// Volontairement prudent : une déclaration vaut pour tout le document, quelle que soit sa
+/// portée ou sa position, et seuls les identifiants nus sont vérifiés, jamais `obj.membre`.
+pub fn undefined_names<'a>(ast: &'a Value, external: &HashSet<String>) -> Vec<(u32, &'a str)> {
+    let mut declared = HashSet::new();
+    declarations(ast, &mut declared);
+
+    let mut found = Vec::new();
+    references(ast, 0, &mut found);
+
+    let mut seen = HashSet::new();
+    found.retain(|&(line, name)| {
+        !declared.contains(name)
+            && !external.contains(name)
+            && !builtins::is_builtin(name)
+            && seen.insert((line, name))
+    });
+    found
+}
+
+/// Tous les noms déclarés dans l'AST : variables, constantes, fonctions et leurs paramètres,
+/// classes et paramètres de leurs méthodes, interfaces, namespaces, enums, itérateurs,
+/// variables d'erreur et paramètres des lambdas.
+fn declarations<'a>(node: &'a Value, declared: &mut HashSet<&'a str>) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            let name = match cmd {
+                Some("set" | "const" | "function" | "class" | "interface" | "namespace" | "enum" | "foreach") => arr.get(2),
+                // ["try", line, body, err_var, catch_body]
+                Some("try" | "try_catch") => arr.get(3),
+                _ => None,
+            };
+            if let Some(name) = name.and_then(|v| v.as_str()) {
+                declared.insert(name);
+            }
+
+            match cmd {
+                // ["function", line, "nom", params, ret, body]
+                Some("function") => params(arr.get(3), declared),
+                // ["lambda", params, body]
+                Some("lambda") => params(arr.get(1), declared),
+                // ["class", line, "nom", {méthode: [params, body, ...]}, ...]
+                Some("class") => {
+                    for method in arr.get(3).and_then(|v| v.as_object()).into_iter().flat_map(|m| m.values()) {
+                        params(method.get(0), declared);
+                    }
+                },
+                _ => {}
+            }
+
+            for item in arr {
+                declarations(item, declared);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                declarations(item, declared);
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Paramètres `["a", ["b", "int"], ...]` (nom seul ou paire nom/type).
+fn params<'a>(params: Option<&'a Value>, declared: &mut HashSet<&'a str>) {
+    for param in params.and_then(|v| v.as_array()).into_iter().flatten() {
+        let name = param.as_str().or_else(|| param.get(0).and_then(|v| v.as_str()));
+        if let Some(name) = name {
+            declared.insert(name);
+        }
+    }
+}
+
+/// Lectures `["get", nom]` de l'AST, avec la ligne de l'instruction englobante. Les listes
+/// qui ne contiennent que des noms (paramètres, variantes d'enum, clés de dictionnaire)
+/// ne sont pas parcourues : une paire comme ["get", "int"] n'y est pas une lecture.
+fn references<'a>(node: &'a Value, line: u32, found: &mut Vec<(u32, &'a str)>) {
+    match node {
+        Value::Array(arr) => {
+            let cmd = arr.first().and_then(|v| v.as_str());
+            // Instruction : ["cmd", line, ...]
+            let line = match (cmd, arr.get(1).and_then(|v| v.as_u64())) {
+                (Some(cmd), Some(l)) if STATEMENTS.contains(&cmd) => l.saturating_sub(1) as u32,
+                _ => line,
+            };
+
+            match cmd {
+                Some("get") if arr.len() == 2 => {
+                    if let Some(name) = arr[1].as_str() {
+                        found.push((line, name));
+                    }
+                    return;
+                },
+                Some("enum" | "interface" | "import") => return,
+                // ["make_dict", [clé, valeur], ...]
+                Some("make_dict") => {
+                    for entry in &arr[1..] {
+                        if let Some(value) = entry.get(1) {
+                            references(value, line, found);
+                        }
+                    }
+                    return;
+                },
+                // Paramètres en position 3, corps en position 5
+                Some("function") => {
+                    for item in arr.iter().skip(4) {
+                        references(item, line, found);
+                    }
+                    return;
+                },
+                Some("lambda") => {
+                    for item in arr.iter().skip(2) {
+                        references(item, line, found);
+                    }
+                    return;
+                },
+                // Méthodes : { nom: [params, body, ...] }
+                Some("class") => {
+                    for method in arr.get(3).and_then(|v| v.as_object()).into_iter().flat_map(|m| m.values()) {
+                        for item in method.as_array().into_iter().flatten().skip(1) {
+                            references(item, line, found);
+                        }
+                    }
+                    for item in arr.iter().skip(4) {
+                        references(item, line, found);
+                    }
+                    return;
+                },
+                _ => {}
+            }
+
+            for item in arr {
+                references(item, line, found);
+            }
+        },
+        Value::Object(map) => {
+            for item in map.values() {
+                references(item, line, found);
+            }
+        },
+        _ => {}
+    }
+}
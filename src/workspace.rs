@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use aegis_core::compiler;
+use serde_json::Value;
+use tower_lsp::lsp_types::{CompletionItemKind, Diagnostic, Location, SymbolInformation, Url};
+
+use crate::diagnostics::{self, Report};
+use crate::document::PositionEncoding;
+use crate::imports;
+use crate::line_index::LineIndex;
+use crate::panics;
+use crate::symbols::{self, SymbolInfo};
+
+/// Entrée de l'index pour un fichier.
+#[derive(Debug)]
+struct IndexedFile {
+    // Dossier du workspace dont provient le fichier (None hors workspace)
+    folder: Option<PathBuf>,
+    symbols: Vec<SymbolInfo>,
+    // Diagnostics du contenu sur disque (None : pas encore indexé depuis le disque)
+    report: Option<Report>,
+}
+
+/// Index des symboles des fichiers Aegis du workspace, y compris ceux qui ne sont pas ouverts.
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    folders: RwLock<Vec<PathBuf>>,
+    files: RwLock<HashMap<Url, IndexedFile>>,
+}
+
+impl WorkspaceIndex {
+    pub fn add_folder(&self, root: PathBuf) {
+        if let Ok(mut folders) = self.folders.write()
+            && !folders.contains(&root) {
+            folders.push(root);
+        }
+    }
+
+    /// Retire un dossier et ses fichiers de l'index. Retourne les Url retirées.
+    pub fn remove_folder(&self, root: &Path) -> Vec<Url> {
+        if let Ok(mut folders) = self.folders.write() {
+            folders.retain(|f| f != root);
+        }
+
+        let Ok(mut files) = self.files.write() else {
+            return Vec::new();
+        };
+        let removed: Vec<Url> = files
+            .iter()
+            .filter(|(_, file)| file.folder.as_deref() == Some(root))
+            .map(|(uri, _)| uri.clone())
+            .collect();
+        for uri in &removed {
+            files.remove(uri);
+        }
+        removed
+    }
+
+    pub fn folders(&self) -> Vec<PathBuf> {
+        self.folders.read().map(|f| f.clone()).unwrap_or_default()
+    }
+
+    /// Dossier du workspace le plus spécifique contenant `uri`.
+    fn folder_of(&self, uri: &Url) -> Option<PathBuf> {
+        let path = uri.to_file_path().ok()?;
+        let folders = self.folders.read().ok()?;
+        folders
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+    }
+
+    /// Indexe tous les fichiers `.aegis` sous `root`. Retourne le nombre de fichiers indexés.
+    pub fn index_folder(&self, root: &Path, encoding: PositionEncoding, max_size: u64) -> usize {
+        aegis_files(root).iter().filter(|path| self.index_file(path, encoding, max_size)).count()
+    }
+
+    /// Compile un fichier depuis le disque et remplace son entrée dans l'index, diagnostics
+    /// compris. Un fichier invalide garde ses symboles précédents ; les fichiers illisibles
+    /// sont ignorés, ceux de plus de `max_size` octets retirés.
+    pub fn index_file(&self, path: &Path, encoding: PositionEncoding, max_size: u64) -> bool {
+        let Ok(uri) = Url::from_file_path(path) else {
+            return false;
+        };
+        if fs::metadata(path).is_ok_and(|meta| meta.len() > max_size) {
+            self.remove(&uri);
+            return false;
+        }
+        let Ok(text) = fs::read_to_string(path) else {
+            return false;
+        };
+
+        // Le lexer comme le loader peuvent paniquer sur une entrée invalide : on isole chaque fichier
+        let analyzed = panics::catch(|| {
+            let compiled = compiler::compile(&text);
+            let found = compiled.as_ref().ok().map(|ast| symbols::extract_symbols(ast, &text, encoding));
+            let external = compiled.as_ref().ok().and_then(|ast| self.imported_names(ast));
+            let imported = compiled.as_ref().map(|ast| self.imported_symbols(ast)).unwrap_or_default();
+            let index = LineIndex::new(&text, encoding);
+            let diagnostics: Vec<Diagnostic> =
+                diagnostics::check(&text, &compiled, found.as_deref().unwrap_or_default(), external.as_ref(), &imported, &index)
+                    .iter()
+                    .map(|e| e.to_lsp(&uri, &text, &index))
+                    .collect();
+            (found, diagnostics)
+        });
+        let Ok((found, diagnostics)) = analyzed else {
+            return false;
+        };
+
+        let folder = self.folder_of(&uri);
+        if let Ok(mut files) = self.files.write() {
+            let previous = files.remove(&uri);
+            // Contenu inchangé : le client garde son rapport
+            let report = match previous.as_ref().and_then(|file| file.report.as_ref()) {
+                Some(report) if report.diagnostics == diagnostics => report.clone(),
+                _ => Report::new(None, diagnostics),
+            };
+            let symbols = found.or_else(|| previous.map(|file| file.symbols)).unwrap_or_default();
+            files.insert(uri, IndexedFile { folder, symbols, report: Some(report) });
+        }
+        true
+    }
+
+    /// Remplace les symboles d'un fichier. Les documents sans chemin sur disque
+    /// (`untitled:` notamment) ne font pas partie du workspace et sont ignorés.
+    pub fn update(&self, uri: Url, symbols: Vec<SymbolInfo>) {
+        if uri.scheme() != "file" {
+            return;
+        }
+
+        let folder = self.folder_of(&uri);
+        if let Ok(mut files) = self.files.write() {
+            let report = files.remove(&uri).and_then(|file| file.report);
+            files.insert(uri, IndexedFile { folder, symbols, report });
+        }
+    }
+
+    /// Diagnostics du contenu sur disque de chaque fichier indexé.
+    pub fn reports(&self) -> Vec<(Url, Report)> {
+        let Ok(files) = self.files.read() else {
+            return Vec::new();
+        };
+        files
+            .iter()
+            .filter_map(|(uri, file)| file.report.clone().map(|report| (uri.clone(), report)))
+            .collect()
+    }
+
+    /// Donne un nouvel identifiant à chaque rapport, pour que le client le redemande en entier.
+    pub fn renew_reports(&self) {
+        let Ok(mut files) = self.files.write() else {
+            return;
+        };
+        for file in files.values_mut() {
+            if let Some(report) = file.report.take() {
+                file.report = Some(Report::new(report.version, report.diagnostics));
+            }
+        }
+    }
+
+    /// Symboles indexés du fichier `uri`.
+    pub fn symbols_of(&self, uri: &Url) -> Option<Vec<SymbolInfo>> {
+        self.files.read().ok()?.get(uri).map(|file| file.symbols.clone())
+    }
+
+    pub fn remove(&self, uri: &Url) {
+        if let Ok(mut files) = self.files.write() {
+            files.remove(uri);
+        }
+    }
+
+    /// Nombre de fichiers indexés.
+    pub fn file_count(&self) -> usize {
+        self.files.read().map(|files| files.len()).unwrap_or_default()
+    }
+
+    /// Noms que les imports de `ast` rendent visibles. Un module importé partage ses globales,
+    /// et les siennes proviennent peut-être d'autres imports : tous les noms du workspace
+    /// sont donc acceptés. None si un import ne correspond à aucun fichier indexé (module
+    /// de la bibliothèque standard, fichier hors workspace...).
+    pub fn imported_names(&self, ast: &Value) -> Option<HashSet<String>> {
+        let imports = imports::imports(ast);
+        if imports.is_empty() {
+            return Some(HashSet::new());
+        }
+
+        let files = self.files.read().ok()?;
+        let indexed = |import: &str| files.keys().any(|uri| is_import_of(uri, import));
+        if !imports.iter().all(|&(_, import)| indexed(import)) {
+            return None;
+        }
+        Some(files.values().flat_map(|file| file.symbols.iter().map(|s| s.name.clone())).collect())
+    }
+
+    /// Symboles exportés des fichiers indexés que `ast` importe directement.
+    pub fn imported_symbols(&self, ast: &Value) -> Vec<SymbolInfo> {
+        let imports = imports::imports(ast);
+        let Ok(files) = self.files.read() else {
+            return Vec::new();
+        };
+        files
+            .iter()
+            .filter(|(uri, _)| imports.iter().any(|&(_, import)| is_import_of(uri, import)))
+            .flat_map(|(_, file)| file.symbols.iter())
+            .filter(|s| is_exported(s.kind))
+            .cloned()
+            .collect()
+    }
+
+    /// Symboles des fichiers indexés dont le nom contient `query`, hors documents `skipped`.
+    pub fn search(&self, query: &str, skipped: &HashSet<Url>) -> Vec<SymbolInformation> {
+        let Ok(files) = self.files.read() else {
+            return Vec::new();
+        };
+        files
+            .iter()
+            .filter(|(uri, _)| !skipped.contains(*uri))
+            .flat_map(|(uri, file)| {
+                file.symbols.iter().filter(|s| s.matches(query)).map(move |s| s.symbol_information(uri))
+            })
+            .collect()
+    }
+
+    /// Symboles visibles depuis les autres fichiers (fonctions, classes, interfaces, namespaces),
+    /// en excluant le document `current`.
+    pub fn exported_symbols(&self, current: &Url) -> Vec<SymbolInfo> {
+        let Ok(files) = self.files.read() else {
+            return Vec::new();
+        };
+
+        files
+            .iter()
+            .filter(|(uri, _)| *uri != current)
+            .flat_map(|(_, file)| file.symbols.iter())
+            .filter(|s| is_exported(s.kind))
+            .cloned()
+            .collect()
+    }
+
+    /// Déclarations de `name` visibles depuis les autres fichiers, dans le seul fichier `uri`.
+    /// None si ce fichier n'est pas indexé.
+    pub fn definitions_in(&self, name: &str, uri: &Url) -> Option<Vec<Location>> {
+        let files = self.files.read().ok()?;
+        let file = files.get(uri)?;
+        Some(
+            file.symbols
+                .iter()
+                .filter(|s| s.name == name && is_exported(s.kind))
+                .map(|s| Location { uri: uri.clone(), range: s.range })
+                .collect(),
+        )
+    }
+
+    /// Déclarations de `name` visibles depuis les autres fichiers, hors document `current`.
+    pub fn definitions(&self, name: &str, current: &Url) -> Vec<Location> {
+        let Ok(files) = self.files.read() else {
+            return Vec::new();
+        };
+
+        files
+            .iter()
+            .filter(|(uri, _)| *uri != current)
+            .flat_map(|(uri, file)| {
+                file.symbols
+                    .iter()
+                    .filter(|s| s.name == name && is_exported(s.kind))
+                    .map(move |s| Location { uri: uri.clone(), range: s.range })
+            })
+            .collect()
+    }
+}
+
+/// Vrai si le fichier `uri` est celui que désigne le chemin `import`.
+fn is_import_of(uri: &Url, import: &str) -> bool {
+    let import = Path::new(import.trim_start_matches("./"));
+    uri.to_file_path().is_ok_and(|path| path.ends_with(import))
+}
+
+/// Vrai pour les symboles visibles depuis les autres fichiers.
+pub fn is_exported(kind: CompletionItemKind) -> bool {
+    matches!(
+        kind,
+        CompletionItemKind::FUNCTION
+            | CompletionItemKind::CLASS
+            | CompletionItemKind::INTERFACE
+            | CompletionItemKind::MODULE
+    )
+}
+
+/// Parcourt récursivement `root` à la recherche de fichiers `.aegis`
+/// (les dossiers cachés comme `.git` sont ignorés). Les liens symboliques sont suivis,
+/// mais chaque dossier réel n'est parcouru qu'une fois : une boucle de liens s'arrête.
+pub fn aegis_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    let mut visited = HashSet::new();
+
+    while let Some(dir) = pending.pop() {
+        let Ok(real) = fs::canonicalize(&dir) else { continue };
+        if !visited.insert(real) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if path.is_dir() {
+                if !hidden {
+                    pending.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "aegis") {
+                files.push(path);
+            }
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle_is_scanned_once() {
+        let root = std::env::temp_dir().join(format!("aegis_files_{}", std::process::id()));
+        let sub = root.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(sub.join("a.aegis"), "print 1\n").unwrap();
+        std::os::unix::fs::symlink("..", sub.join("loop")).unwrap();
+
+        let files = aegis_files(&root);
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with("sub/a.aegis"));
+    }
+}